@@ -1,168 +1,178 @@
+use crate::nbt::CompressionOptions;
+use crate::nbt::Endianness;
 use crate::nbt::FileType;
+use crate::nbt::NbtEncodeError;
+use crate::nbt::NbtMode;
+use crate::nbt::NbtWriter;
 use crate::nbt::TagType;
-use crate::nbt::ANONYMOUS_KEY;
-use bytes::BufMut;
-use flate2::read::GzEncoder;
-use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use indexmap::IndexMap;
+use serde_json::Value;
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::io::Read;
+use std::io::Write;
+use std::str::FromStr;
 
+/// Encodes a root tag into binary NBT compressed in `file_type`, replacing the content of `nbt`.
 pub fn to_nbt<'a>(
-    json: HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    json: IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     nbt: &mut Vec<u8>,
     file_type: FileType,
-) {
-    let mut buf: Vec<u8> = vec![];
-    for elem in json.iter() {
-        walk(&mut buf, elem, false);
-    }
+    endianness: Endianness,
+    mode: NbtMode,
+) -> Result<(), NbtEncodeError> {
+    to_nbt_with(
+        json,
+        nbt,
+        file_type,
+        endianness,
+        mode,
+        &CompressionOptions::default(),
+    )
+}
+
+/// Encodes a root tag like [`to_nbt`], compressing it with the level and gzip header of
+/// `options`.
+pub fn to_nbt_with<'a>(
+    json: IndexMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut Vec<u8>,
+    file_type: FileType,
+    endianness: Endianness,
+    mode: NbtMode,
+    options: &CompressionOptions,
+) -> Result<(), NbtEncodeError> {
     nbt.clear();
+    let level = options.compression();
     match file_type {
-        FileType::Raw => nbt.append(&mut buf),
+        FileType::Raw => {
+            write_root(&json, NbtWriter::new(nbt, endianness, mode))?;
+        }
         FileType::GZip => {
-            let mut e = GzEncoder::new(&*buf, Compression::fast());
-            e.read_to_end(nbt).unwrap();
+            let e = options.gz_builder().write(nbt, level);
+            write_root(&json, NbtWriter::new(e, endianness, mode))?.finish()?;
+        }
+        FileType::Zlib => {
+            let e = ZlibEncoder::new(nbt, level);
+            write_root(&json, NbtWriter::new(e, endianness, mode))?.finish()?;
+        }
+        // LZ4 blocks are compressed as a whole.
+        FileType::Lz4 => {
+            let buf = write_root(&json, NbtWriter::new(vec![], endianness, mode))?;
+            nbt.extend(file_type.compress_with(buf, options));
         }
     }
+    Ok(())
 }
 
-fn walk<'a>(nbt: &mut Vec<u8>, elem: (&Cow<'a, [u8]>, &TagType<'a>), is_anonymous: bool) {
-    let (k, v) = elem;
-    match v {
-        TagType::End => nbt.put_u8(0x00),
-        TagType::Byte(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x01);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            nbt.put_i8(*v)
-        }
-        TagType::Short(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x02);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            nbt.put_i16(*v)
-        }
-        TagType::Int(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x03);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            nbt.put_i32(*v)
-        }
-        TagType::Long(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x04);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            nbt.put_i64(v.clone().into())
-        }
-        TagType::Float(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x05);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            nbt.put_f32(*v);
-        }
-        TagType::Double(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x06);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            nbt.put_f64(*v);
-        }
-        TagType::ByteArray(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x07);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            let len = v.len() as i32;
-            nbt.put_i32(len);
-            for v in v {
-                nbt.put_i8(*v);
-            }
-        }
-        TagType::String(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x08);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            let len = v.len() as u16;
-            nbt.put_u16(len);
-            for c in v.iter() {
-                nbt.put_u8(*c);
-            }
+fn write_root<W: Write>(
+    json: &IndexMap<Cow<[u8]>, TagType>,
+    mut nbt: NbtWriter<W>,
+) -> Result<W, NbtEncodeError> {
+    for (name, value) in json.iter() {
+        nbt.write_tag_raw(name, value)?;
+    }
+    nbt.finish()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Indent {
+    Spaces(u8),
+    Tab,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+impl FromStr for Indent {
+    type Err = String;
+
+    /// Parses a number of spaces or `tab`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("tab") {
+            return Ok(Indent::Tab);
         }
-        TagType::List(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x09);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            let tag_type = u8::from(&v[0]);
-            let len = v.len() as i32;
-            nbt.put_u8(tag_type);
-            nbt.put_i32(len);
-            for i in v.iter() {
-                walk(nbt, (&ANONYMOUS_KEY, i), true);
-            }
+        s.parse()
+            .map(Indent::Spaces)
+            .map_err(|_| format!("Invalid indent \"{}\".", s))
+    }
+}
+
+/// The layout of written JSON documents.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct JsonStyle {
+    /// The indentation of nested values, or `None` to write the whole document on one line.
+    pub indent: Option<Indent>,
+    /// Keeps arrays holding only numbers, such as UUIDs and heightmaps, on one line when indenting.
+    pub compact_numeric_arrays: bool,
+}
+
+/// Writes a JSON document in the given style.
+pub fn to_string(json: &Value, style: &JsonStyle) -> String {
+    let mut buf = String::new();
+    write_value(&mut buf, json, style, 0);
+    buf
+}
+
+// Writes a value nested at depth in a document
+pub(crate) fn write_value(buf: &mut String, json: &Value, style: &JsonStyle, depth: usize) {
+    match style.indent {
+        Some(indent) => write_pretty(buf, json, indent, style.compact_numeric_arrays, depth),
+        None => buf.push_str(&json.to_string()),
+    }
+}
+
+pub(crate) fn put_indent(buf: &mut String, indent: Indent, depth: usize) {
+    buf.push('\n');
+    for _ in 0..depth {
+        match indent {
+            Indent::Spaces(n) => buf.extend((0..n).map(|_| ' ')),
+            Indent::Tab => buf.push('\t'),
         }
-        TagType::Compound(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x0a);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            for elem in v.iter() {
-                walk(nbt, elem, false);
-            }
-            walk(nbt, (&ANONYMOUS_KEY, &TagType::End), false);
+    }
+}
+
+fn write_pretty(buf: &mut String, json: &Value, indent: Indent, compact: bool, depth: usize) {
+    match json {
+        Value::Array(v) if v.is_empty() => buf.push_str("[]"),
+        Value::Array(v) if compact && v.iter().all(Value::is_number) => {
+            buf.push('[');
+            for (i, v) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push_str(", ");
+                }
+                buf.push_str(&v.to_string());
+            }
+            buf.push(']');
         }
-        TagType::IntArray(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x0b);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            let len = v.len() as i32;
-            nbt.put_i32(len);
-            for i in v.iter() {
-                walk(nbt, (&ANONYMOUS_KEY, &TagType::Int(*i)), true);
-            }
+        Value::Array(v) => {
+            buf.push('[');
+            for (i, v) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push(',');
+                }
+                put_indent(buf, indent, depth + 1);
+                write_pretty(buf, v, indent, compact, depth + 1);
+            }
+            put_indent(buf, indent, depth);
+            buf.push(']');
         }
-        TagType::LongArray(v) => {
-            if !is_anonymous {
-                let name_len = k.len() as u16;
-                nbt.put_u8(0x0c);
-                nbt.put_u16(name_len);
-                nbt.put_slice(k);
-            }
-            let len = v.len() as i32;
-            nbt.put_i32(len);
-            for i in v.iter() {
-                walk(nbt, (&ANONYMOUS_KEY, &TagType::Long(i.clone())), true);
-            }
+        Value::Object(v) if v.is_empty() => buf.push_str("{}"),
+        Value::Object(v) => {
+            buf.push('{');
+            for (i, (k, v)) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push(',');
+                }
+                put_indent(buf, indent, depth + 1);
+                buf.push_str(&Value::String(k.clone()).to_string());
+                buf.push_str(": ");
+                write_pretty(buf, v, indent, compact, depth + 1);
+            }
+            put_indent(buf, indent, depth);
+            buf.push('}');
         }
+        v => buf.push_str(&v.to_string()),
     }
 }
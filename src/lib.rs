@@ -1,3 +1,7 @@
 pub mod json;
 
 pub mod nbt;
+
+pub mod region;
+
+pub mod snbt;
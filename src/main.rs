@@ -1,26 +1,84 @@
 use clap::ArgAction;
+use clap::Args;
 use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
+use indexmap::IndexMap;
 use nbt_json::json;
+use nbt_json::json::Indent;
+use nbt_json::json::JsonStyle;
 use nbt_json::nbt;
+use nbt_json::nbt::CompressionOptions;
+use nbt_json::nbt::Endianness;
 use nbt_json::nbt::FileType;
-use rust_i18n::i18n;
+use nbt_json::nbt::GzipHeader;
+use nbt_json::nbt::JsonEmitError;
+use nbt_json::nbt::JsonFormat;
+use nbt_json::nbt::NbtMode;
+use nbt_json::nbt::NbtReader;
+use nbt_json::nbt::NbtValue;
+use nbt_json::nbt::Schema;
+use nbt_json::nbt::TagDiffKind;
+use nbt_json::nbt::TagType;
+use nbt_json::nbt::TypeHints;
+use nbt_json::region;
+use nbt_json::region::Chunk;
+use nbt_json::region::Region;
+use nbt_json::snbt;
+use rayon::prelude::*;
 use rust_i18n::t;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde::Serialize;
 use serde_json::error::Category;
-use std::collections::HashMap;
-use std::convert::AsRef;
+use serde_json::Map;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::env;
+use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
 use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
 use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
 use std::process;
 use std::string::ToString;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::LazyLock;
+use translations::*;
+use walkdir::WalkDir;
 
-i18n!("locales", fallback = ["en_US", "ja_JP"]);
+// The translations have a module of their own, as they import names like Cow.
+mod translations {
+    rust_i18n::i18n!("locales", fallback = ["en_US", "ja_JP"]);
+}
+
+const REGION_INDEX: &str = "index.json";
+// The filename reading the standard input
+const STDIN: &str = "-";
 
 static HELP_TEMPLATE: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "{}\n{}",
+        *HELP_COMMAND_TEMPLATE,
+        color_print::cformat!(
+            "<strong><u>{}:</u></strong>\n{}\n",
+            t!("help.commands"),
+            "{subcommands}"
+        )
+    )
+});
+
+// Without the section of subcommands
+static HELP_COMMAND_TEMPLATE: LazyLock<String> = LazyLock::new(|| {
     color_print::cformat!(
         "\
 {}
@@ -44,11 +102,45 @@ static HELP_TEMPLATE: LazyLock<String> = LazyLock::new(|| {
 });
 
 static HELP_ABOUT: LazyLock<String> = LazyLock::new(|| t!("help.about").to_string());
+static HELP_COMPACT_ARRAYS: LazyLock<String> =
+    LazyLock::new(|| t!("help.compact-arrays").to_string());
+static HELP_COMPRESSION_LEVEL: LazyLock<String> =
+    LazyLock::new(|| t!("help.compression-level").to_string());
+static HELP_CONTINUE_ON_ERROR: LazyLock<String> =
+    LazyLock::new(|| t!("help.continue-on-error").to_string());
+static HELP_DIFF: LazyLock<String> = LazyLock::new(|| t!("help.diff").to_string());
+static HELP_EDITION: LazyLock<String> = LazyLock::new(|| t!("help.edition").to_string());
+static HELP_EXTENSION: LazyLock<String> = LazyLock::new(|| t!("help.extension").to_string());
 static HELP_FILENAME: LazyLock<String> = LazyLock::new(|| t!("help.filename").to_string());
+static HELP_FILENAMES: LazyLock<String> = LazyLock::new(|| t!("help.filenames").to_string());
 static HELP_FILETYPE: LazyLock<String> = LazyLock::new(|| t!("help.filetype").to_string());
 static HELP_FORCE: LazyLock<String> = LazyLock::new(|| t!("help.force").to_string());
+static HELP_FROM: LazyLock<String> = LazyLock::new(|| t!("help.from").to_string());
+static HELP_GET: LazyLock<String> = LazyLock::new(|| t!("help.get").to_string());
+static HELP_GZIP_HEADER: LazyLock<String> = LazyLock::new(|| t!("help.gzip-header").to_string());
 static HELP_HELP: LazyLock<String> = LazyLock::new(|| t!("help.help").to_string());
+static HELP_HINTS: LazyLock<String> = LazyLock::new(|| t!("help.hints").to_string());
+static HELP_INDENT: LazyLock<String> = LazyLock::new(|| t!("help.indent").to_string());
+static HELP_IN_PLACE: LazyLock<String> = LazyLock::new(|| t!("help.in-place").to_string());
+static HELP_INFO: LazyLock<String> = LazyLock::new(|| t!("help.info").to_string());
+static HELP_JSON_FORMAT: LazyLock<String> = LazyLock::new(|| t!("help.json-format").to_string());
+static HELP_NETWORK: LazyLock<String> = LazyLock::new(|| t!("help.network").to_string());
+static HELP_OTHER: LazyLock<String> = LazyLock::new(|| t!("help.other").to_string());
 static HELP_OUT: LazyLock<String> = LazyLock::new(|| t!("help.out").to_string());
+static HELP_OUTPUT_DIR: LazyLock<String> = LazyLock::new(|| t!("help.output-dir").to_string());
+static HELP_PATH: LazyLock<String> = LazyLock::new(|| t!("help.path").to_string());
+static HELP_PRETTY: LazyLock<String> = LazyLock::new(|| t!("help.pretty").to_string());
+static HELP_SCHEMA: LazyLock<String> = LazyLock::new(|| t!("help.schema").to_string());
+static HELP_SET: LazyLock<String> = LazyLock::new(|| t!("help.set").to_string());
+static HELP_SORT_KEYS: LazyLock<String> = LazyLock::new(|| t!("help.sort-keys").to_string());
+static HELP_STORAGE_VERSION: LazyLock<String> =
+    LazyLock::new(|| t!("help.storage-version").to_string());
+static HELP_TO: LazyLock<String> = LazyLock::new(|| t!("help.to").to_string());
+static HELP_TO_JSON: LazyLock<String> = LazyLock::new(|| t!("help.to-json").to_string());
+static HELP_TO_NBT: LazyLock<String> = LazyLock::new(|| t!("help.to-nbt").to_string());
+static HELP_TO_SNBT: LazyLock<String> = LazyLock::new(|| t!("help.to-snbt").to_string());
+static HELP_VALIDATE: LazyLock<String> = LazyLock::new(|| t!("help.validate").to_string());
+static HELP_VALUE: LazyLock<String> = LazyLock::new(|| t!("help.value").to_string());
 static HELP_VERSION: LazyLock<String> = LazyLock::new(|| t!("help.version").to_string());
 
 fn main() {
@@ -58,127 +150,1248 @@ fn main() {
         }
     }
     let cli = Cli::parse();
-    let path = Path::new(&cli.filename);
-    let out_path = if let Some(ref out) = cli.out {
-        if out == "-" {
-            None
-        } else {
-            Some(Path::new(out))
+    match cli.command {
+        Some(Command::ToJson {
+            filenames,
+            input,
+            output,
+            batch,
+        }) => convert(&filenames, Some(Format::Json), &input, &output, &batch),
+        Some(Command::ToNbt {
+            filenames,
+            input,
+            output,
+            batch,
+        }) => convert(&filenames, Some(Format::Nbt), &input, &output, &batch),
+        Some(Command::ToSnbt {
+            filenames,
+            input,
+            output,
+            batch,
+        }) => convert(&filenames, Some(Format::Snbt), &input, &output, &batch),
+        Some(Command::Info { filename, input }) => info(&filename, &input),
+        Some(Command::Get {
+            filename,
+            path,
+            input,
+        }) => get(&filename, &path, &input),
+        Some(Command::Set {
+            filename,
+            path,
+            value,
+            input,
+            output,
+        }) => set(&filename, &path, &value, &input, &output),
+        Some(Command::Diff {
+            filename,
+            other,
+            input,
+        }) => diff(&filename, &other, &input),
+        Some(Command::Validate { filename, input }) => validate(&filename, &input),
+        None => convert(&cli.filenames, cli.to, &cli.input, &cli.output, &cli.batch),
+    }
+}
+
+/// A format of a single NBT document, chosen by --from and --to, the file extension or the
+/// content.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+#[value(rename_all = "lower")]
+enum Format {
+    Nbt,
+    Json,
+    Snbt,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "dat" | "dat_old" | "litematic" | "nbt" | "schem" | "schematic" => Some(Format::Nbt),
+            "json" => Some(Format::Json),
+            "snbt" => Some(Format::Snbt),
+            _ => None,
         }
-    } else {
-        None
+    }
+
+    // Detects the format of a file with another extension by its content. Text is checked first,
+    // as whitespace like \n is also the type of a raw NBT root.
+    fn sniff(data: &[u8]) -> Option<Self> {
+        match data.trim_ascii_start().first() {
+            // SNBT like {Count:1b} is no JSON.
+            Some(b'{') => match serde_json::from_slice::<IgnoredAny>(data) {
+                Ok(_) => Some(Format::Json),
+                Err(_) => Some(Format::Snbt),
+            },
+            // JSON documents are objects, while SNBT may be a list or an array like [B;1b].
+            Some(b'[') => Some(Format::Snbt),
+            _ => nbt::get_file_type(data).map(|_| Format::Nbt),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Nbt => "NBT",
+            Format::Json => "JSON",
+            Format::Snbt => "SNBT",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Nbt => "dat",
+            Format::Json => "json",
+            Format::Snbt => "snbt",
+        }
+    }
+
+    // The format converted to when neither --to nor the output filename tells
+    fn default_target(self) -> Self {
+        match self {
+            Format::Nbt => Format::Json,
+            Format::Json | Format::Snbt => Format::Nbt,
+        }
+    }
+}
+
+/// A document read by [`read_document`], with what is kept when writing it back.
+struct Document<'a> {
+    format: Format,
+    // The compression of NBT
+    file_type: Option<FileType>,
+    // The storage version of the header of Bedrock Edition level.dat
+    storage_version: Option<u32>,
+    tags: IndexMap<Cow<'a, [u8]>, TagType<'a>>,
+}
+
+// Converts documents, or a region file from or to a directory of JSON files
+fn convert(
+    filenames: &[String],
+    to: Option<Format>,
+    input: &InputArgs,
+    output: &OutputArgs,
+    batch: &BatchArgs,
+) {
+    let conversion = Conversion {
+        to,
+        hints: input.hints_file(),
+        input,
+        output,
     };
-    if !path.exists() {
-        eprintln!("{}", t!("error.file-not-found", filename = cli.filename));
-        process::exit(1);
+    let [filename] = filenames else {
+        convert_batch(filenames, &conversion, batch);
+        return;
+    };
+    let path = Path::new(filename);
+    let is_region_dir = path.join(REGION_INDEX).exists();
+    if output.output_dir.is_some() || path.is_dir() && !is_region_dir || is_glob(filename) {
+        convert_batch(filenames, &conversion, batch);
+        return;
     }
-    if let Some(out_path) = out_path {
-        if out_path.exists() {
-            if cli.force {
-                eprintln!(
-                    "{}",
-                    t!(
-                        "warn.out-file-already-exist",
-                        filename = cli.out.clone().unwrap()
-                    )
-                );
-            } else {
-                eprintln!(
-                    "{}",
-                    t!("error.out-file-already-exist", filename = cli.out.unwrap())
-                );
-                process::exit(1);
+    check_exists(filename).or_fail();
+    let out_path = output.out_path();
+    let is_region = path
+        .extension()
+        .is_some_and(|ext| ext == "mca" || ext == "mcr");
+    if is_region && matches!(to, None | Some(Format::Json)) {
+        let out_dir = match out_path {
+            Some(out_path) => out_path.to_path_buf(),
+            None => path.with_extension(""),
+        };
+        output.check_out(&out_dir);
+        dump_region(output, path, &out_dir);
+        return;
+    }
+    if is_region_dir && matches!(to, None | Some(Format::Nbt)) {
+        let out_path = match out_path {
+            Some(out_path) => out_path.to_path_buf(),
+            None => {
+                let mut name = path.as_os_str().to_os_string();
+                name.push(".mca");
+                PathBuf::from(name)
             }
+        };
+        output.check_out(&out_path);
+        pack_region(input, output, path, &out_path);
+        return;
+    }
+    let relative = path.file_name().map_or(path, Path::new);
+    conversion.convert_file(filename, relative).or_fail();
+}
+
+/// The options shared by the documents of a conversion.
+struct Conversion<'a> {
+    to: Option<Format>,
+    // The hints of --hints, to which the schema of each document is added
+    hints: TypeHints,
+    input: &'a InputArgs,
+    output: &'a OutputArgs,
+}
+
+impl Conversion<'_> {
+    // The format given by --to, -o or --extension
+    fn explicit_target(&self) -> Option<Format> {
+        self.to.or_else(|| self.output.format())
+    }
+
+    // The format a document is converted to. A file is rewritten in place in its own format
+    // unless told otherwise.
+    fn target(&self, format: Format) -> Format {
+        self.explicit_target()
+            .unwrap_or(match self.output.in_place {
+                true => format,
+                false => format.default_target(),
+            })
+    }
+
+    // The output file of a file with a known extension
+    fn out_file(&self, file: &Path, relative: &Path) -> Option<PathBuf> {
+        let to = self.target(Format::from_path(file)?);
+        self.output
+            .out_file(&file.to_string_lossy(), relative, to.extension())
+    }
+
+    // Converts a document to the output file of OutputArgs::out_file, where relative is its path
+    // in --output-dir
+    fn convert_file(&self, filename: &str, relative: &Path) -> Result<(), String> {
+        let (input, output) = (self.input, self.output);
+        if self.is_streamed(filename) {
+            return self.stream_to_json(filename, relative);
         }
+        let schema = self.schema(filename);
+        let hints = with_schema(self.hints.clone(), schema);
+        // The tags borrow strings from the parsed JSON.
+        let mut json = Map::new();
+        let mut document = read_document(filename, input, &hints, &mut json)?;
+        let to = self.target(document.format);
+        self.check_in_place(filename, document.format, to)?;
+        if schema.is_some() {
+            for violation in document.tags.values().flat_map(|tag| hints.validate(tag)) {
+                eprintln!("{}", t!("warn.schema-mismatch", reason = violation));
+            }
+        }
+        if output.sort_keys {
+            document.tags.values_mut().for_each(TagType::sort_keys);
+        }
+        let out = output.out_file(filename, relative, to.extension());
+        if let Some(out) = &out {
+            output.check_out_file(out)?;
+        }
+        let file_type = output.filetype.or(document.file_type);
+        let storage_version = output.storage_version.or(document.storage_version);
+        let data = encode(document.tags, to, file_type, storage_version, input, output)?;
+        output.write(out.as_deref(), data)
+    }
+
+    // Fails if the file would be rewritten in place in another format, which would leave only the
+    // backup in the original format
+    fn check_in_place(&self, filename: &str, format: Format, to: Format) -> Result<(), String> {
+        match self.output.in_place && to != format {
+            true => Err(t!(
+                "error.in-place-format",
+                filename = filename,
+                from = format.name(),
+                to = to.name()
+            )
+            .to_string()),
+            false => Ok(()),
+        }
+    }
+
+    // The schema of --schema, or else the one guessed by the input or output filename
+    fn schema(&self, filename: &str) -> Option<Schema> {
+        self.input
+            .schema
+            .or_else(|| Schema::from_path(Path::new(filename)))
+            .or_else(|| self.output.out_path().and_then(Schema::from_path))
+    }
+
+    // Whether the document is NBT converted to JSON as it is read. The whole document is needed
+    // to sort the keys, write typed objects or check it against the schema, and the header of
+    // Bedrock Edition level.dat is told by the length of the whole file.
+    fn is_streamed(&self, filename: &str) -> bool {
+        let (input, output) = (self.input, self.output);
+        let format = input
+            .from
+            .or_else(|| Format::from_path(Path::new(filename)));
+        format == Some(Format::Nbt)
+            && self.target(Format::Nbt) == Format::Json
+            && output.json_format == JsonFormat::Compact
+            && !output.sort_keys
+            && self.schema(filename).is_none()
+            && input.edition == Edition::Java
     }
-    match path.extension() {
-        Some(ext) if ext == "dat" => {
-            let nbt = fs::read(path);
-            if let Err(err) = nbt {
-                eprintln!("{}", t!("error.read-failed", reason = err.kind()));
-                process::exit(1);
+
+    // Converts NBT to JSON as it is read, see is_streamed
+    fn stream_to_json(&self, filename: &str, relative: &Path) -> Result<(), String> {
+        let (input, output) = (self.input, self.output);
+        self.check_in_place(filename, Format::Nbt, Format::Json)?;
+        check_exists(filename)?;
+        let out = output.out_file(filename, relative, Format::Json.extension());
+        if let Some(out) = &out {
+            output.check_out_file(out)?;
+        }
+        let decoding_failed = |err| t!("error.nbt-decoding-failed", reason = err).to_string();
+        let data =
+            open_input(filename).map_err(|err| t!("error.read-failed", reason = err.kind()))?;
+        let (file_type, data) = nbt::detect_decoder(data).map_err(decoding_failed)?;
+        let mut nbt = NbtReader::new(data, Endianness::Big, input.mode());
+        let file_type = output.filetype.or(Some(file_type));
+        output.write_with(out.as_deref(), |out| {
+            match nbt::write_json(&mut nbt, out, file_type, output.json_style()) {
+                Ok(_) => Ok(()),
+                Err(JsonEmitError::Decode(err)) => Err(decoding_failed(err)),
+                Err(JsonEmitError::Io(kind)) => Err(output_failed(kind)),
             }
-            let nbt = nbt.unwrap();
-            let mut json = HashMap::new();
-            nbt::to_json(nbt, &mut json);
-            let json = serde_json::to_string(&json).unwrap();
-            output(cli.out, path, json.into_bytes());
-        }
-        Some(ext) if ext == "json" => {
-            let json = fs::read(path);
-            if let Err(err) = json {
-                eprintln!("{}", t!("error.read-failed", reason = err.kind()));
-                process::exit(1);
+        })
+    }
+}
+
+// Converts the documents in parallel, mirroring the input directories in --output-dir
+fn convert_batch(filenames: &[String], conversion: &Conversion, batch: &BatchArgs) {
+    if conversion.output.out.is_some() {
+        fail(t!("error.out-with-many-inputs").to_string());
+    }
+    // Files already in the format they are converted to are no sources in directories.
+    let target = conversion
+        .explicit_target()
+        .filter(|_| !conversion.output.in_place);
+    let (files, errors) = collect_files(filenames, target);
+    for (filename, err) in errors.iter() {
+        eprintln!("{}: {}", filename, err);
+    }
+    // Files converted into another input, like x.dat and x.json, would read and write each other
+    // at once.
+    let inputs = files
+        .iter()
+        .filter_map(|(file, _)| fs::canonicalize(file).ok())
+        .collect::<HashSet<_>>();
+    let (files, clashing): (Vec<_>, Vec<_>) = files.into_iter().partition(|(file, relative)| {
+        let out = conversion.out_file(file, relative);
+        let out = out.and_then(|v| fs::canonicalize(v).ok());
+        out.is_none_or(|v| !inputs.contains(&v) || fs::canonicalize(file).is_ok_and(|w| v == w))
+    });
+    for (file, _) in clashing.iter() {
+        eprintln!(
+            "{}",
+            t!("warn.output-is-input", filename = file.to_string_lossy())
+        );
+    }
+    let failed = AtomicBool::new(!errors.is_empty());
+    // None for the files skipped after a failure
+    let results = files
+        .par_iter()
+        .map(|(file, relative)| {
+            if !batch.continue_on_error && failed.load(Ordering::Relaxed) {
+                return None;
             }
-            let json = json.unwrap();
-            let json = serde_json::from_slice(&json);
-            if let Err(err) = json {
-                let cat = err.classify();
-                if cat == Category::Io {
-                    eprintln!(
-                        "{}",
-                        t!("error.invalid-json", reason = err.io_error_kind().unwrap())
-                    );
-                } else {
-                    eprintln!(
-                        "{}",
-                        t!("error.invalid-json", reason = format!("{:?}", cat))
-                    );
+            let filename = file.to_string_lossy();
+            let result = conversion.convert_file(&filename, relative);
+            if let Err(err) = &result {
+                eprintln!("{}: {}", filename, err);
+                failed.store(true, Ordering::Relaxed);
+            }
+            Some(result.is_ok())
+        })
+        .collect::<Vec<_>>();
+    let count = |v: Option<bool>| results.iter().filter(|w| **w == v).count();
+    let failed = count(Some(false)) + errors.len();
+    let skipped = count(None) + clashing.len();
+    eprintln!(
+        "{}",
+        t!(
+            "info.summary",
+            converted = count(Some(true)),
+            failed = failed,
+            skipped = skipped
+        )
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+// A file to convert with its path relative to --output-dir
+type BatchFile = (PathBuf, PathBuf);
+// An input which cannot be converted with the error
+type BatchError = (String, String);
+
+// The files to convert with their paths relative to --output-dir. Directories are walked
+// recursively for files of known extensions other than the target format, keeping their names,
+// and glob patterns are expanded keeping the directories below the pattern. Missing files and
+// invalid patterns are returned apart with their errors, failing like the files themselves.
+fn collect_files(
+    filenames: &[String],
+    target: Option<Format>,
+) -> (Vec<BatchFile>, Vec<BatchError>) {
+    let mut files = vec![];
+    let mut errors = vec![];
+    for filename in filenames {
+        let path = Path::new(filename);
+        if path.is_dir() {
+            let base = path.parent().unwrap_or(Path::new(""));
+            let entries = WalkDir::new(path)
+                .sort_by_file_name()
+                .into_iter()
+                // Directories of dumped regions are no documents.
+                .filter_entry(|v| !v.path().join(REGION_INDEX).exists())
+                .filter_map(Result::ok)
+                .filter(|v| v.file_type().is_file())
+                .filter(|v| Format::from_path(v.path()).is_some_and(|v| Some(v) != target));
+            for entry in entries {
+                let relative = entry.path().strip_prefix(base).unwrap_or(entry.path());
+                files.push((entry.path().to_path_buf(), relative.to_path_buf()));
+            }
+        } else if is_glob(filename) {
+            let paths = match glob::glob(filename) {
+                Ok(paths) => paths,
+                Err(err) => {
+                    let err = t!("error.invalid-pattern", reason = err).to_string();
+                    errors.push((filename.clone(), err));
+                    continue;
                 }
-                process::exit(1);
+            };
+            let base = glob_base(filename);
+            let len = files.len();
+            for path in paths.filter_map(Result::ok).filter(|v| v.is_file()) {
+                let relative = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+                files.push((path, relative));
             }
-            let json = json.unwrap();
-            let mut buf = HashMap::new();
-            if let Err(err) = nbt::resolve_tag_types(&mut buf, &json) {
-                eprintln!("{}", t!("error.tag-resolving-failed", reason = err));
-                process::exit(1);
+            // A pattern matching nothing is likely mistyped.
+            if files.len() == len {
+                let err = t!("error.file-not-found", filename = filename).to_string();
+                errors.push((filename.clone(), err));
             }
-            let mut nbt = Vec::new();
-            json::to_nbt(buf, &mut nbt, cli.filetype);
-            output(cli.out, path, nbt);
+        } else if let Err(err) = check_exists(filename) {
+            errors.push((filename.clone(), err));
+        } else {
+            let relative = path.file_name().map_or(path, Path::new);
+            files.push((path.to_path_buf(), relative.to_path_buf()));
         }
-        _ => {}
     }
+    (files, errors)
+}
+
+// A pattern rather than the name of an existing file
+fn is_glob(filename: &str) -> bool {
+    !Path::new(filename).exists() && filename.contains(['*', '?', '['])
 }
 
-fn output(out: Option<String>, path: &Path, data: Vec<u8>) {
-    match out {
-        Some(name) if &*name == "-" => {
-            if let Err(err) = io::stdout().write(&data) {
-                eprintln!("{}", t!("error.output-failed", reason = err.kind()));
-                process::exit(1);
+// The directories of a glob pattern before the first one with wildcards
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|v| !v.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+fn create_parent_dir(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent)
+            .map_err(|err| t!("error.output-failed", reason = err.kind()).to_string()),
+        None => Ok(()),
+    }
+}
+
+// Prints an error message and exits
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+trait OrFail<T> {
+    // Exits with the error message of a failure
+    fn or_fail(self) -> T;
+}
+
+impl<T> OrFail<T> for Result<T, String> {
+    fn or_fail(self) -> T {
+        self.unwrap_or_else(|err| fail(err))
+    }
+}
+
+fn check_exists(filename: &str) -> Result<(), String> {
+    if filename != STDIN && !Path::new(filename).exists() {
+        return Err(t!("error.file-not-found", filename = filename).to_string());
+    }
+    Ok(())
+}
+
+// Fails unless forced
+fn check_overwrite(filename: &str, force: bool) -> Result<(), String> {
+    if !force {
+        return Err(t!("error.out-file-already-exist", filename = filename).to_string());
+    }
+    eprintln!("{}", t!("warn.out-file-already-exist", filename = filename));
+    Ok(())
+}
+
+// Reads a document, applying the hints to JSON
+fn read_document<'a>(
+    filename: &str,
+    input: &InputArgs,
+    hints: &TypeHints,
+    json: &'a mut Map<String, Value>,
+) -> Result<Document<'a>, String> {
+    check_exists(filename)?;
+    let path = Path::new(filename);
+    let data = read_input(filename).map_err(|err| t!("error.read-failed", reason = err.kind()))?;
+    let format = input
+        .from
+        .or_else(|| Format::from_path(path))
+        .or_else(|| Format::sniff(&data))
+        .ok_or_else(|| t!("error.unknown-format", filename = filename))?;
+    let mut document = Document {
+        format,
+        file_type: None,
+        storage_version: None,
+        tags: IndexMap::new(),
+    };
+    document.tags = match format {
+        Format::Nbt => {
+            let endianness = input.edition.endianness();
+            let header = match endianness {
+                Endianness::Little => nbt::split_bedrock_header(&data),
+                Endianness::Big => None,
+            };
+            document.storage_version = header.map(|(version, _)| version);
+            document.file_type = nbt::get_file_type(header.map_or(&data[..], |(_, body)| body));
+            nbt::read(data, endianness, input.mode())
+                .map_err(|err| t!("error.nbt-decoding-failed", reason = err))?
+        }
+        Format::Json => {
+            *json = match serde_json::from_slice(&data) {
+                Ok(mut json) => {
+                    hints.apply(&mut json);
+                    json
+                }
+                Err(err) => return Err(invalid_json(err)),
+            };
+            let mut buf = IndexMap::new();
+            nbt::resolve_tag_types(&mut buf, json)
+                .map_err(|err| t!("error.tag-resolving-failed", reason = err))?;
+            // The compression and the header of the NBT it was converted from
+            document.file_type = nbt::compression(json)
+                .map_err(|err| t!("error.tag-resolving-failed", reason = err))?;
+            document.storage_version = nbt::storage_version(json)
+                .map_err(|err| t!("error.tag-resolving-failed", reason = err))?;
+            buf
+        }
+        Format::Snbt => {
+            let tag = match String::from_utf8(data) {
+                Ok(snbt) => snbt::parse(&snbt).map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            // SNBT has no root name.
+            let tag = tag.map_err(|err| t!("error.snbt-parsing-failed", reason = err))?;
+            IndexMap::from([(b""[..].into(), tag)])
+        }
+    };
+    Ok(document)
+}
+
+// Opens the file, or the standard input for -
+fn open_input(filename: &str) -> io::Result<Box<dyn Read>> {
+    if filename == STDIN {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(filename)?)))
+    }
+}
+
+// Reads the whole standard input for -
+fn read_input(filename: &str) -> io::Result<Vec<u8>> {
+    if filename == STDIN {
+        let mut buf = vec![];
+        io::stdin().read_to_end(&mut buf)?;
+        Ok(buf)
+    } else {
+        fs::read(filename)
+    }
+}
+
+// The root tag of a document as a value, with its name
+fn root_value(tags: IndexMap<Cow<[u8]>, TagType>) -> Result<(Vec<u8>, NbtValue), String> {
+    let (name, tag) = tags
+        .into_iter()
+        .next()
+        .ok_or_else(|| t!("error.tag-not-found", path = ""))?;
+    let value =
+        NbtValue::try_from(tag).map_err(|err| t!("error.tag-resolving-failed", reason = err))?;
+    Ok((name.into_owned(), value))
+}
+
+// Encodes the tags, recording the compression and the storage version in JSON so that NBT is
+// restored in them
+fn encode(
+    tags: IndexMap<Cow<[u8]>, TagType>,
+    to: Format,
+    file_type: Option<FileType>,
+    storage_version: Option<u32>,
+    input: &InputArgs,
+    output: &OutputArgs,
+) -> Result<Vec<u8>, String> {
+    Ok(match to {
+        Format::Nbt => {
+            let mut nbt = Vec::new();
+            json::to_nbt_with(
+                tags,
+                &mut nbt,
+                file_type.unwrap_or(FileType::Raw),
+                input.edition.endianness(),
+                input.mode(),
+                &output.compression(),
+            )
+            .map_err(|err| t!("error.nbt-encoding-failed", reason = err))?;
+            match storage_version {
+                Some(version) => nbt::put_bedrock_header(version, nbt),
+                None => nbt,
             }
         }
-        Some(name) => {
-            if let Err(err) = fs::write(name, data) {
-                eprintln!("{}", t!("error.output-failed", reason = err.kind()));
-                process::exit(1);
+        Format::Json => {
+            let mut json = Map::new();
+            nbt::tag_types_to_json_as(&tags, &mut json, output.json_format);
+            if let Some(file_type) = file_type {
+                nbt::set_compression(&mut json, file_type);
+            }
+            if let Some(version) = storage_version {
+                nbt::set_storage_version(&mut json, version);
+            }
+            json::to_string(&Value::Object(json), &output.json_style()).into_bytes()
+        }
+        Format::Snbt => match tags.values().next() {
+            Some(tag) => snbt::to_string(tag).into_bytes(),
+            None => vec![],
+        },
+    })
+}
+
+fn info(filename: &str, input: &InputArgs) {
+    let mut json = Map::new();
+    let document = read_document(filename, input, &input.hints_for(filename), &mut json).or_fail();
+    println!("{}: {}", t!("info.format"), document.format.name());
+    if let Some(file_type) = document.file_type.and_then(|v| v.to_possible_value()) {
+        println!("{}: {}", t!("info.compression"), file_type.get_name());
+    }
+    if let Some(version) = document.storage_version {
+        println!("{}: {}", t!("info.storage-version"), version);
+    }
+    for (name, tag) in document.tags.iter() {
+        let (count, depth) = count_tags(tag);
+        let name = cesu8::from_java_cesu8(name).map_or_else(
+            |_| String::from_utf8_lossy(name).into_owned(),
+            Cow::into_owned,
+        );
+        println!("{}: {:?}", t!("info.root-name"), name);
+        println!(
+            "{}: {}",
+            t!("info.root-type"),
+            TagType::u8_to_str(u8::from(tag)).unwrap_or("?")
+        );
+        println!("{}: {}", t!("info.tags"), count);
+        println!("{}: {}", t!("info.depth"), depth);
+    }
+}
+
+// The number of tags and the depth of the tree, counting the root tag
+fn count_tags(tag: &TagType) -> (usize, usize) {
+    let (count, depth) = match tag {
+        TagType::List(_, v) => v.iter().map(count_tags).fold((0, 0), sum_counts),
+        TagType::Compound(v) => v.values().map(count_tags).fold((0, 0), sum_counts),
+        _ => (0, 0),
+    };
+    (count + 1, depth + 1)
+}
+
+fn sum_counts((count, depth): (usize, usize), (n, d): (usize, usize)) -> (usize, usize) {
+    (count + n, depth.max(d))
+}
+
+fn get(filename: &str, tag_path: &str, input: &InputArgs) {
+    let mut json = Map::new();
+    let document = read_document(filename, input, &input.hints_for(filename), &mut json).or_fail();
+    let (_, root) = root_value(document.tags).or_fail();
+    match root.get_path(tag_path) {
+        Some(value) => println!("{}", snbt::to_string(&value.clone().into())),
+        None => fail(t!("error.tag-not-found", path = tag_path).to_string()),
+    }
+}
+
+// Writes back to the file unless another output is given, keeping its format and compression
+fn set(filename: &str, tag_path: &str, value: &str, input: &InputArgs, output: &OutputArgs) {
+    let value = match snbt::parse(value) {
+        Ok(value) => NbtValue::try_from(value),
+        Err(err) => fail(t!("error.snbt-parsing-failed", reason = err).to_string()),
+    };
+    let value = match value {
+        Ok(value) => value,
+        Err(err) => fail(t!("error.tag-resolving-failed", reason = err).to_string()),
+    };
+    let path = Path::new(filename);
+    let mut json = Map::new();
+    let document = read_document(filename, input, &input.hints_for(filename), &mut json).or_fail();
+    let to = output.format().unwrap_or(document.format);
+    let file_type = output.filetype.or(document.file_type);
+    let storage_version = output.storage_version.or(document.storage_version);
+    let (name, mut root) = root_value(document.tags).or_fail();
+    if root.set_path(tag_path, value).is_err() {
+        fail(t!("error.tag-not-settable", path = tag_path).to_string());
+    }
+    let mut tags = IndexMap::from([(Cow::Owned(name), TagType::from(root))]);
+    if output.sort_keys {
+        tags.values_mut().for_each(TagType::sort_keys);
+    }
+    let data = encode(tags, to, file_type, storage_version, input, output).or_fail();
+    let relative = path.file_name().map_or(path, Path::new);
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let out = output.out_file(filename, relative, &extension);
+    // The file itself is overwritten without -f.
+    if let Some(out) = out.as_deref().filter(|v| *v != path) {
+        output.check_out_file(out).or_fail();
+    }
+    output.write(out.as_deref(), data).or_fail();
+}
+
+// Lists the differences like `~ Data.Time: 100L -> 200L`, failing if there are any
+fn diff(filename: &str, other: &str, input: &InputArgs) {
+    let mut json = Map::new();
+    let document = read_document(filename, input, &input.hints_for(filename), &mut json).or_fail();
+    let (_, root) = root_value(document.tags).or_fail();
+    let mut other_json = Map::new();
+    let other_document =
+        read_document(other, input, &input.hints_for(other), &mut other_json).or_fail();
+    let (_, other_root) = root_value(other_document.tags).or_fail();
+    let to_snbt = |v: &NbtValue| snbt::to_string(&v.clone().into());
+    let diffs = root.diff(&other_root);
+    for diff in diffs.iter() {
+        match &diff.kind {
+            TagDiffKind::Added(v) => println!("+ {}: {}", diff.path, to_snbt(v)),
+            TagDiffKind::Removed(v) => println!("- {}: {}", diff.path, to_snbt(v)),
+            TagDiffKind::Changed(v, w) => {
+                println!("~ {}: {} -> {}", diff.path, to_snbt(v), to_snbt(w))
             }
         }
-        None => {
-            let name = path.with_extension("json");
-            if let Err(err) = fs::write(name, data) {
-                eprintln!("{}", t!("error.output-failed", reason = err.kind()));
-                process::exit(1);
+    }
+    if !diffs.is_empty() {
+        process::exit(1);
+    }
+}
+
+// Reports every violation of the schema, failing if there are any
+fn validate(filename: &str, input: &InputArgs) {
+    check_exists(filename).or_fail();
+    let hints = input.hints_for(filename);
+    if hints.is_empty() {
+        fail(t!("error.schema-not-found", filename = filename).to_string());
+    }
+    let mut json = Map::new();
+    let document = read_document(filename, input, &hints, &mut json).or_fail();
+    let violations = document
+        .tags
+        .values()
+        .flat_map(|tag| hints.validate(tag))
+        .collect::<Vec<_>>();
+    for violation in violations.iter() {
+        println!("{}", violation);
+    }
+    if !violations.is_empty() {
+        fail(t!("error.validation-failed", count = violations.len()).to_string());
+    }
+}
+
+fn invalid_json(err: serde_json::Error) -> String {
+    let cat = err.classify();
+    if cat == Category::Io {
+        t!("error.invalid-json", reason = err.io_error_kind().unwrap()).to_string()
+    } else {
+        t!("error.invalid-json", reason = format!("{:?}", cat)).to_string()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ChunkIndex {
+    x: u8,
+    z: u8,
+    timestamp: u32,
+    filetype: FileType,
+}
+
+fn chunk_file_name(x: u8, z: u8) -> String {
+    format!("c.{}.{}.json", x, z)
+}
+
+fn dump_region(output: &OutputArgs, path: &Path, out_dir: &Path) {
+    let data = fs::read(path)
+        .map_err(|err| t!("error.read-failed", reason = err.kind()).to_string())
+        .or_fail();
+    let region = region::read(&data)
+        .map_err(|err| t!("error.region-reading-failed", reason = err).to_string())
+        .or_fail();
+    let output_failed = |err: io::Error| t!("error.output-failed", reason = err.kind()).to_string();
+    fs::create_dir_all(out_dir).map_err(output_failed).or_fail();
+    let mut index = vec![];
+    for chunk in region.chunks {
+        let mut tags = chunk
+            .decode()
+            .map_err(|err| t!("error.nbt-decoding-failed", reason = err).to_string())
+            .or_fail();
+        if output.sort_keys {
+            tags.values_mut().for_each(TagType::sort_keys);
+        }
+        let mut json = Map::new();
+        nbt::tag_types_to_json_as(&tags, &mut json, output.json_format);
+        let json = json::to_string(&Value::Object(json), &output.json_style());
+        fs::write(out_dir.join(chunk_file_name(chunk.x, chunk.z)), json)
+            .map_err(output_failed)
+            .or_fail();
+        index.push(ChunkIndex {
+            x: chunk.x,
+            z: chunk.z,
+            timestamp: chunk.timestamp,
+            filetype: chunk.file_type,
+        });
+    }
+    let index = json::to_string(&serde_json::to_value(index).unwrap(), &output.json_style());
+    fs::write(out_dir.join(REGION_INDEX), index)
+        .map_err(output_failed)
+        .or_fail();
+}
+
+fn pack_region(input: &InputArgs, output: &OutputArgs, path: &Path, out_path: &Path) {
+    let hints = input.type_hints(input.schema);
+    let read_failed = |err: io::Error| t!("error.read-failed", reason = err.kind()).to_string();
+    let index = fs::read(path.join(REGION_INDEX))
+        .map_err(read_failed)
+        .or_fail();
+    let index: Vec<ChunkIndex> = serde_json::from_slice(&index)
+        .map_err(invalid_json)
+        .or_fail();
+    let mut region = Region::default();
+    for entry in index {
+        let json = fs::read(path.join(chunk_file_name(entry.x, entry.z)))
+            .map_err(read_failed)
+            .or_fail();
+        let mut json = serde_json::from_slice(&json)
+            .map_err(invalid_json)
+            .or_fail();
+        hints.apply(&mut json);
+        let mut buf = IndexMap::new();
+        nbt::resolve_tag_types(&mut buf, &json)
+            .map_err(|err| t!("error.tag-resolving-failed", reason = err).to_string())
+            .or_fail();
+        let mut nbt = Vec::new();
+        json::to_nbt(buf, &mut nbt, FileType::Raw, Endianness::Big, NbtMode::File)
+            .map_err(|err| t!("error.nbt-encoding-failed", reason = err).to_string())
+            .or_fail();
+        region.chunks.push(Chunk {
+            x: entry.x,
+            z: entry.z,
+            timestamp: entry.timestamp,
+            file_type: entry.filetype,
+            nbt,
+        });
+    }
+    let region = region::write_with(&region, &output.compression())
+        .map_err(|err| t!("error.region-writing-failed", reason = err).to_string())
+        .or_fail();
+    fs::write(out_path, region)
+        .map_err(|err| t!("error.output-failed", reason = err.kind()).to_string())
+        .or_fail();
+}
+
+// Copies a file to <name>.bak, or <name>.<n>.bak if taken, so that no older backup is replaced
+fn back_up(path: &Path) -> Result<(), String> {
+    let backup_failed = |err: io::Error| t!("error.backup-failed", reason = err.kind()).to_string();
+    let mut n = 0;
+    loop {
+        let mut backup = path.as_os_str().to_os_string();
+        if n > 0 {
+            backup.push(format!(".{}", n));
+        }
+        backup.push(".bak");
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&backup)
+        {
+            Ok(mut backup) => {
+                let mut file = File::open(path).map_err(backup_failed)?;
+                io::copy(&mut file, &mut backup).map_err(backup_failed)?;
+                let permissions = file.metadata().map_err(backup_failed)?.permissions();
+                return backup.set_permissions(permissions).map_err(backup_failed);
             }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(err) => return Err(backup_failed(err)),
+        }
+    }
+}
+
+// Writes a file through a temporary file next to it, which replaces it only once fully written.
+// The replaced file is backed up first if told so.
+fn write_atomically(
+    path: &Path,
+    keep_backup: bool,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(format!(".{}.tmp", process::id()));
+    let temp = PathBuf::from(temp);
+    let result = (|| {
+        let failed = |err: io::Error| output_failed(err.kind());
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .map_err(failed)?;
+        // A replaced file keeps its permissions.
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())
+                .map_err(failed)?;
         }
+        let mut writer = BufWriter::new(&mut file);
+        write(&mut writer)?;
+        writer.flush().map_err(failed)?;
+        drop(writer);
+        file.sync_all().map_err(failed)?;
+        if keep_backup && path.exists() {
+            back_up(path)?;
+        }
+        fs::rename(&temp, path).map_err(failed)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
     }
+    result
+}
+
+fn output_failed(kind: io::ErrorKind) -> String {
+    t!("error.output-failed", reason = kind).to_string()
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+#[value(rename_all = "lower")]
+enum Edition {
+    Java,
+    Bedrock,
+}
+
+impl Edition {
+    fn endianness(self) -> Endianness {
+        match self {
+            Edition::Java => Endianness::Big,
+            Edition::Bedrock => Endianness::Little,
+        }
+    }
+}
+
+/// Without subcommands, converts a file in the direction told by --to, the output filename or
+/// the input format.
 #[derive(Debug, Parser)]
-#[command(about = HELP_ABOUT.as_str(), author, disable_help_flag = true, disable_version_flag = true, help_template = HELP_TEMPLATE.as_str(), long_about = None, version
+#[command(about = HELP_ABOUT.as_str(), args_conflicts_with_subcommands = true, author, disable_help_flag = true, disable_help_subcommand = true, disable_version_flag = true, help_template = HELP_TEMPLATE.as_str(), long_about = None, subcommand_negates_reqs = true, version
 )]
 struct Cli {
-    #[arg(help = HELP_FILENAME.as_str())]
-    filename: String,
-    #[arg(default_value = "raw", help = HELP_FILETYPE.as_str(), long, short = 't')]
-    filetype: FileType,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    batch: BatchArgs,
+    // Required without subcommands
+    #[arg(help = HELP_FILENAMES.as_str(), required = true)]
+    filenames: Vec<String>,
+    #[arg(action = ArgAction::Help, global = true, help = HELP_HELP.as_str(), long, short)]
+    help: Option<bool>,
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[arg(help = HELP_TO.as_str(), long)]
+    to: Option<Format>,
+    #[arg(action = ArgAction::Version, help = HELP_VERSION.as_str(), long, short = 'V')]
+    version: Option<bool>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(about = HELP_TO_JSON.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    ToJson {
+        #[arg(help = HELP_FILENAMES.as_str(), required = true)]
+        filenames: Vec<String>,
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+    #[command(about = HELP_TO_NBT.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    ToNbt {
+        #[arg(help = HELP_FILENAMES.as_str(), required = true)]
+        filenames: Vec<String>,
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+    #[command(about = HELP_TO_SNBT.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    ToSnbt {
+        #[arg(help = HELP_FILENAMES.as_str(), required = true)]
+        filenames: Vec<String>,
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+    #[command(about = HELP_INFO.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    Info {
+        #[arg(help = HELP_FILENAME.as_str())]
+        filename: String,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    #[command(about = HELP_GET.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    Get {
+        #[arg(help = HELP_FILENAME.as_str())]
+        filename: String,
+        #[arg(help = HELP_PATH.as_str())]
+        path: String,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    #[command(about = HELP_SET.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    Set {
+        #[arg(help = HELP_FILENAME.as_str())]
+        filename: String,
+        #[arg(help = HELP_PATH.as_str())]
+        path: String,
+        #[arg(help = HELP_VALUE.as_str())]
+        value: String,
+        #[command(flatten)]
+        input: InputArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    #[command(about = HELP_DIFF.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    Diff {
+        #[arg(help = HELP_FILENAME.as_str())]
+        filename: String,
+        #[arg(help = HELP_OTHER.as_str())]
+        other: String,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    #[command(about = HELP_VALIDATE.as_str(), disable_help_flag = true, help_template = HELP_COMMAND_TEMPLATE.as_str())]
+    Validate {
+        #[arg(help = HELP_FILENAME.as_str())]
+        filename: String,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+}
+
+/// Options for reading documents, of which the edition and --network also apply to writing NBT.
+#[derive(Args, Debug)]
+struct InputArgs {
+    #[arg(default_value = "java", help = HELP_EDITION.as_str(), long, short)]
+    edition: Edition,
+    #[arg(help = HELP_FROM.as_str(), long)]
+    from: Option<Format>,
+    #[arg(help = HELP_HINTS.as_str(), long)]
+    hints: Option<PathBuf>,
+    #[arg(help = HELP_NETWORK.as_str(), long, short)]
+    network: bool,
+    #[arg(help = HELP_SCHEMA.as_str(), long)]
+    schema: Option<Schema>,
+}
+
+/// Options for converting many files.
+#[derive(Args, Debug)]
+struct BatchArgs {
+    #[arg(help = HELP_CONTINUE_ON_ERROR.as_str(), long)]
+    continue_on_error: bool,
+}
+
+/// Options for writing documents.
+#[derive(Args, Debug)]
+struct OutputArgs {
+    #[arg(help = HELP_COMPACT_ARRAYS.as_str(), long)]
+    compact_arrays: bool,
+    #[arg(
+        default_value_t = 1,
+        help = HELP_COMPRESSION_LEVEL.as_str(),
+        long,
+        short = 'l',
+        value_parser = clap::value_parser!(u32).range(0..=9)
+    )]
+    compression_level: u32,
+    #[arg(help = HELP_EXTENSION.as_str(), long, short = 'x')]
+    extension: Option<String>,
+    #[arg(help = HELP_FILETYPE.as_str(), long, short = 't')]
+    filetype: Option<FileType>,
     #[arg(help = HELP_FORCE.as_str(), long, short)]
     force: bool,
-    #[arg(action = ArgAction::Help, help = HELP_HELP.as_str(), long, short)]
-    help: Option<bool>,
+    #[arg(default_value = "zeroed", help = HELP_GZIP_HEADER.as_str(), long)]
+    gzip_header: GzipHeader,
+    #[arg(
+        conflicts_with_all = ["extension", "out", "output_dir"],
+        help = HELP_IN_PLACE.as_str(),
+        long,
+        short = 'i'
+    )]
+    in_place: bool,
+    #[arg(help = HELP_INDENT.as_str(), long)]
+    indent: Option<Indent>,
+    #[arg(default_value = "compact", help = HELP_JSON_FORMAT.as_str(), long)]
+    json_format: JsonFormat,
     #[arg(help = HELP_OUT.as_str(), long, short)]
     out: Option<String>,
-    #[arg(action = ArgAction::Version, help = HELP_VERSION.as_str(), long, short = 'V')]
-    version: Option<bool>,
+    #[arg(help = HELP_OUTPUT_DIR.as_str(), long)]
+    output_dir: Option<PathBuf>,
+    #[arg(help = HELP_PRETTY.as_str(), long, short)]
+    pretty: bool,
+    #[arg(help = HELP_SORT_KEYS.as_str(), long)]
+    sort_keys: bool,
+    #[arg(help = HELP_STORAGE_VERSION.as_str(), long)]
+    storage_version: Option<u32>,
+}
+
+impl InputArgs {
+    // The hints of --hints, followed by the ones of --schema or the schema guessed by the filename
+    fn hints_for(&self, filename: &str) -> TypeHints {
+        self.type_hints(
+            self.schema
+                .or_else(|| Schema::from_path(Path::new(filename))),
+        )
+    }
+
+    // The hints of --hints, followed by the ones of the schema
+    fn type_hints(&self, schema: Option<Schema>) -> TypeHints {
+        with_schema(self.hints_file(), schema)
+    }
+
+    // Empty without --hints
+    fn hints_file(&self) -> TypeHints {
+        let Some(path) = &self.hints else {
+            return TypeHints::default();
+        };
+        let hints = fs::read_to_string(path)
+            .map_err(|err| t!("error.read-failed", reason = err.kind()).to_string())
+            .or_fail();
+        let hints = match path.extension().and_then(OsStr::to_str) {
+            Some("toml") => TypeHints::from_toml(&hints),
+            _ => TypeHints::from_json(&hints),
+        };
+        hints
+            .map_err(|err| t!("error.hints-loading-failed", reason = err).to_string())
+            .or_fail()
+    }
+
+    fn mode(&self) -> NbtMode {
+        if self.network {
+            NbtMode::Network
+        } else {
+            NbtMode::File
+        }
+    }
+}
+
+// Adds the hints of the schema after the others
+fn with_schema(mut hints: TypeHints, schema: Option<Schema>) -> TypeHints {
+    if let Some(schema) = schema {
+        // The path is valid.
+        let _ = hints.extend_at("", &schema.hints());
+    }
+    hints
+}
+
+impl OutputArgs {
+    fn compression(&self) -> CompressionOptions {
+        CompressionOptions {
+            level: self.compression_level,
+            header: self.gzip_header,
+        }
+    }
+
+    fn json_style(&self) -> JsonStyle {
+        JsonStyle {
+            // --indent implies --pretty.
+            indent: self.indent.or(self.pretty.then(Indent::default)),
+            compact_numeric_arrays: self.compact_arrays,
+        }
+    }
+
+    // The format told by the extension of -o or --extension
+    fn format(&self) -> Option<Format> {
+        self.out_path().and_then(Format::from_path).or_else(|| {
+            let extension = self.extension.as_deref()?;
+            Format::from_path(&Path::new("_").with_extension(extension.trim_start_matches('.')))
+        })
+    }
+
+    // The output file of a document, unless written to the standard output: -o, the input file
+    // itself with --in-place, or else the input file at its relative path in --output-dir, with
+    // --extension or the default extension. The standard input is written to the standard output
+    // unless -o is given.
+    fn out_file(&self, filename: &str, relative: &Path, extension: &str) -> Option<PathBuf> {
+        if let Some(out) = &self.out {
+            return (out != "-").then(|| PathBuf::from(out));
+        }
+        if filename == STDIN {
+            return None;
+        }
+        if self.in_place {
+            return Some(PathBuf::from(filename));
+        }
+        let path = match &self.output_dir {
+            Some(output_dir) => output_dir.join(relative),
+            None => PathBuf::from(filename),
+        };
+        let extension = self.extension.as_deref().unwrap_or(extension);
+        Some(path.with_extension(extension.trim_start_matches('.')))
+    }
+
+    // Fails if the output file already exists, unless forced or rewritten in place
+    fn check_out_file(&self, out: &Path) -> Result<(), String> {
+        match out.exists() && !self.in_place {
+            true => check_overwrite(&out.to_string_lossy(), self.force),
+            false => Ok(()),
+        }
+    }
+
+    // Writes the encoded document to the output file, or else to the standard output
+    fn write(&self, out: Option<&Path>, data: Vec<u8>) -> Result<(), String> {
+        self.write_with(out, |out| {
+            out.write_all(&data)
+                .map_err(|err| output_failed(err.kind()))
+        })
+    }
+
+    // Writes a document to the output file as it is encoded by write, or else to the standard
+    // output. The file is only replaced once fully written, after being backed up with
+    // --in-place.
+    fn write_with(
+        &self,
+        out: Option<&Path>,
+        write: impl FnOnce(&mut dyn Write) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let Some(out) = out else {
+            let mut stdout = BufWriter::new(io::stdout().lock());
+            write(&mut stdout)?;
+            return stdout.flush().map_err(|err| output_failed(err.kind()));
+        };
+        create_parent_dir(out)?;
+        write_atomically(out, self.in_place, write)
+    }
+
+    // The output file, unless written to the standard output
+    fn out_path(&self) -> Option<&Path> {
+        self.out.as_deref().filter(|v| *v != "-").map(Path::new)
+    }
+
+    // Fails if the output file or directory of a region already exists, unless forced
+    fn check_out(&self, out: &Path) {
+        if out.exists() {
+            check_overwrite(&out.to_string_lossy(), self.force).or_fail();
+        }
+    }
 }
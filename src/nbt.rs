@@ -1,26 +1,93 @@
 use bytes::Buf;
+use bytes::BufMut;
 use clap::ValueEnum;
 use flate2::read::GzDecoder;
+use flate2::read::ZlibDecoder;
+use flate2::read::ZlibEncoder;
+use flate2::Compression;
+use flate2::GzBuilder;
+use indexmap::IndexMap;
+use path::PathSegment;
+use serde::Deserialize;
+use serde::Serialize;
 use serde_json::Map;
 use serde_json::Number;
 use serde_json::Value;
 use std::borrow::Cow;
-use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::io::BufRead;
+use std::io;
 use std::io::Read;
 use std::str::FromStr;
 use std::sync::LazyLock;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
+mod array;
+mod de;
+mod diff;
+mod emitter;
+mod hints;
+mod lz4;
+mod path;
+mod reader;
 mod resolver;
+mod schema;
+mod ser;
+mod typed;
+mod value;
+mod writer;
+
+pub use array::ByteArray;
+pub use array::IntArray;
+pub use array::LongArray;
+pub use de::from_slice;
+pub use de::from_tag;
+pub use diff::TagDiff;
+pub use diff::TagDiffKind;
+pub use emitter::write_json;
+pub use emitter::JsonEmitError;
+pub use emitter::JsonEmitter;
+pub use hints::SchemaViolation;
+pub use hints::SchemaViolationKind;
+pub use hints::TypeHints;
+pub use hints::TypeHintsError;
+pub use reader::NbtEvent;
+pub use reader::NbtReader;
+pub use schema::Schema;
+pub use ser::to_tag;
+pub use ser::to_vec;
+pub use value::NbtCompound;
+pub use value::NbtValue;
+pub use writer::NbtEncodeError;
+pub use writer::NbtWriter;
 
 pub(crate) static ANONYMOUS_KEY: LazyLock<Cow<[u8]>> = LazyLock::new(|| cesu8::to_java_cesu8(""));
 
 const JS_MAX_SAFE_INTEGER: i64 = 2i64.pow(53) - 1;
 
+/// The version of the JSON format written by [`tag_types_to_json`].
+pub const JSON_FORMAT_VERSION: u64 = 1;
+/// The key of the version in JSON documents.
+pub const VERSION_KEY: &str = "$version";
+/// The key of the [`JsonFormat`] in JSON documents, omitted for [`JsonFormat::Compact`].
+pub const FORMAT_KEY: &str = "$format";
+/// The key of the [`FileType`] of the source NBT in JSON documents, see [`compression`].
+pub const COMPRESSION_KEY: &str = "$compression";
+/// The key of the storage version of the header of Bedrock Edition `level.dat` in JSON
+/// documents, see [`storage_version`].
+pub const STORAGE_VERSION_KEY: &str = "$storageVersion";
+
+// The keys of the metadata of JSON documents in their order
+const METADATA_KEYS: [&str; 4] = [
+    VERSION_KEY,
+    COMPRESSION_KEY,
+    STORAGE_VERSION_KEY,
+    FORMAT_KEY,
+];
+
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum LongNumber {
     Number(i64),
@@ -56,8 +123,9 @@ pub enum TagType<'a> {
     Double(f64),
     ByteArray(Vec<i8>),
     String(Cow<'a, [u8]>),
-    List(Vec<TagType<'a>>),
-    Compound(HashMap<Cow<'a, [u8]>, TagType<'a>>),
+    /// The item tag type and the items
+    List(u8, Vec<TagType<'a>>),
+    Compound(IndexMap<Cow<'a, [u8]>, TagType<'a>>),
     IntArray(Vec<i32>),
     LongArray(Vec<LongNumber>),
 }
@@ -100,6 +168,18 @@ impl TagType<'_> {
             _ => Err("Unknown tag type"),
         }
     }
+
+    /// Sorts the entries of compounds by name recursively, for canonical output.
+    pub fn sort_keys(&mut self) {
+        match self {
+            TagType::List(_, v) => v.iter_mut().for_each(TagType::sort_keys),
+            TagType::Compound(v) => {
+                v.sort_keys();
+                v.values_mut().for_each(TagType::sort_keys);
+            }
+            _ => {}
+        }
+    }
 }
 
 impl From<&TagType<'_>> for u8 {
@@ -114,7 +194,7 @@ impl From<&TagType<'_>> for u8 {
             TagType::Double(_) => 0x06,
             TagType::ByteArray(_) => 0x07,
             TagType::String(_) => 0x08,
-            TagType::List(_) => 0x09,
+            TagType::List(..) => 0x09,
             TagType::Compound(_) => 0x0a,
             TagType::IntArray(_) => 0x0b,
             TagType::LongArray(_) => 0x0c,
@@ -122,298 +202,721 @@ impl From<&TagType<'_>> for u8 {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum TagResolvingError {
     UnsupportedValue,
+    InvalidRoot,
+    UnsupportedVersion,
+    UnsupportedFormat,
+    UnsupportedCompression,
+    InvalidStorageVersion,
+    /// Keys of a compound naming the same tag, e.g. `a;B` and `a;S`, with the path of the tag
+    DuplicateName(String),
+}
+
+impl TagResolvingError {
+    // Prefixes the path with the compound entry or list item it was raised in
+    pub(crate) fn within(self, segment: PathSegment) -> Self {
+        match self {
+            TagResolvingError::DuplicateName(inner) => {
+                let mut path = path::to_string(&[segment]);
+                if !inner.starts_with('[') {
+                    path.push('.');
+                }
+                path.push_str(&inner);
+                TagResolvingError::DuplicateName(path)
+            }
+            err => err,
+        }
+    }
 }
 
 impl Display for TagResolvingError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             TagResolvingError::UnsupportedValue => write!(f, "Unsupported value."),
+            TagResolvingError::InvalidRoot => write!(f, "Exactly one root tag is required."),
+            TagResolvingError::UnsupportedVersion => {
+                write!(f, "Unsupported version of the JSON format.")
+            }
+            TagResolvingError::UnsupportedFormat => write!(f, "Unsupported JSON format."),
+            TagResolvingError::UnsupportedCompression => write!(f, "Unsupported compression."),
+            TagResolvingError::InvalidStorageVersion => write!(f, "Invalid storage version."),
+            TagResolvingError::DuplicateName(path) => write!(f, "Duplicate tag \"{}\".", path),
         }
     }
 }
 
 impl Error for TagResolvingError {}
 
+/// Resolves a JSON document into its root tag.
+///
+/// The document is an object holding a single entry, the root tag, keyed by the root name in
+/// the same way as compound entries, e.g. `{"": {...}}` for the usual unnamed root compound.
+/// It may also hold the version of the format under [`VERSION_KEY`], which is assumed to be
+/// [`JSON_FORMAT_VERSION`] when omitted, the [`JsonFormat`] under [`FORMAT_KEY`] and the
+/// compression under [`COMPRESSION_KEY`] and [`STORAGE_VERSION_KEY`]. A `$` starting the key of
+/// the root tag is doubled to tell it from these.
 pub fn resolve_tag_types<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     json: &'a Map<String, Value>,
 ) -> Result<(), TagResolvingError> {
-    resolver::resolve_compound(nbt, ANONYMOUS_KEY.clone(), json)?;
-    Ok(())
+    if let Some(version) = json.get(VERSION_KEY) {
+        if version.as_u64() != Some(JSON_FORMAT_VERSION) {
+            return Err(TagResolvingError::UnsupportedVersion);
+        }
+    }
+    let format = match json.get(FORMAT_KEY) {
+        None => JsonFormat::Compact,
+        Some(format) => format
+            .as_str()
+            .and_then(|v| JsonFormat::from_str(v, false).ok())
+            .ok_or(TagResolvingError::UnsupportedFormat)?,
+    };
+    let mut root = json
+        .iter()
+        .filter(|(k, _)| !METADATA_KEYS.contains(&k.as_str()))
+        .map(|(k, v)| {
+            (
+                k.strip_prefix('$')
+                    .filter(|k| k.starts_with('$'))
+                    .unwrap_or(k),
+                v,
+            )
+        });
+    match (root.next(), root.next(), format) {
+        (Some((k, v)), None, JsonFormat::Compact) => resolver::resolve_entry(nbt, k, v),
+        (Some((k, v)), None, JsonFormat::Typed) => typed::resolve_entry(nbt, k, v),
+        _ => Err(TagResolvingError::InvalidRoot),
+    }
+}
+
+/// Reads the compression of the source NBT recorded in a JSON document by [`set_compression`],
+/// so that converting it back restores the original file.
+pub fn compression(json: &Map<String, Value>) -> Result<Option<FileType>, TagResolvingError> {
+    match json.get(COMPRESSION_KEY) {
+        None => Ok(None),
+        Some(file_type) => file_type
+            .as_str()
+            .and_then(|v| FileType::from_str(v, true).ok())
+            .map(Some)
+            .ok_or(TagResolvingError::UnsupportedCompression),
+    }
+}
+
+/// Records the compression of the source NBT in a JSON document, next to its version.
+pub fn set_compression(json: &mut Map<String, Value>, file_type: FileType) {
+    let name = file_type_name(file_type);
+    insert_metadata(json, COMPRESSION_KEY, Value::String(name));
+}
+
+/// Reads the storage version of the header of Bedrock Edition `level.dat` recorded in a JSON
+/// document by [`set_storage_version`], so that converting it back restores the header.
+pub fn storage_version(json: &Map<String, Value>) -> Result<Option<u32>, TagResolvingError> {
+    match json.get(STORAGE_VERSION_KEY) {
+        None => Ok(None),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .map(Some)
+            .ok_or(TagResolvingError::InvalidStorageVersion),
+    }
+}
+
+/// Records the storage version of the header of Bedrock Edition `level.dat` in a JSON document,
+/// next to its compression.
+pub fn set_storage_version(json: &mut Map<String, Value>, version: u32) {
+    insert_metadata(json, STORAGE_VERSION_KEY, Value::from(version));
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+// Inserts metadata after the preceding ones, e.g. $compression after $version
+fn insert_metadata(json: &mut Map<String, Value>, key: &str, value: Value) {
+    let order = |k: &str| METADATA_KEYS.iter().position(|v| *v == k);
+    let index = json
+        .keys()
+        .position(|k| order(k).is_none_or(|v| v > order(key).unwrap()))
+        .unwrap_or(json.len());
+    json.shift_insert(index, key.to_string(), value);
+}
+
+// The name of a file type in JSON documents, e.g. gzip
+fn file_type_name(file_type: FileType) -> String {
+    file_type
+        .to_possible_value()
+        .unwrap()
+        .get_name()
+        .to_string()
+}
+
+/// The encoding of tags in JSON documents.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum JsonFormat {
+    /// Values with their types in suffixes of the keys, e.g. `{"Count;B": 1}`
+    #[default]
+    Compact,
+    /// Objects naming their types, e.g. `{"Count": {"type": "byte", "value": 1}}`
+    Typed,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 #[value(rename_all = "lower")]
 pub enum FileType {
     Raw,
     #[value(alias = "gz")]
     GZip,
+    Zlib,
+    Lz4,
 }
 
-pub fn get_file_type(nbt: &[u8]) -> FileType {
-    let mut nbt = nbt;
-    let mut first_word = vec![];
-    let _ = nbt.read_until(2, &mut first_word);
-    let first_word = (first_word[0] as u16) << 8 | first_word[1] as u16;
-    match first_word {
-        0x0a00 => FileType::Raw,
-        0x1f8b => FileType::GZip,
-        _ => unreachable!(),
+impl FileType {
+    pub fn decompress(self, nbt: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        match self {
+            FileType::Raw => return Ok(nbt),
+            FileType::GZip => {
+                let mut d = GzDecoder::new(&nbt[..]);
+                d.read_to_end(&mut buf)?;
+            }
+            FileType::Zlib => {
+                let mut d = ZlibDecoder::new(&nbt[..]);
+                d.read_to_end(&mut buf)?;
+            }
+            FileType::Lz4 => return lz4::decompress(&nbt),
+        }
+        Ok(buf)
     }
-}
 
-pub fn to_json(nbt: Vec<u8>, json: &mut HashMap<String, Value>) {
-    let nbt = {
-        let mut buf = vec![];
-        match get_file_type(&nbt) {
-            FileType::Raw => {
-                buf = nbt;
+    /// Wraps a reader of NBT compressed in this file type into a reader of the decompressed NBT.
+    ///
+    /// LZ4 is decompressed as a whole, as its blocks are not streamed.
+    pub fn decoder<'a, R: Read + 'a>(self, mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            FileType::Raw => Box::new(reader),
+            FileType::GZip => Box::new(GzDecoder::new(reader)),
+            FileType::Zlib => Box::new(ZlibDecoder::new(reader)),
+            FileType::Lz4 => {
+                let mut buf = vec![];
+                reader.read_to_end(&mut buf)?;
+                Box::new(io::Cursor::new(lz4::decompress(&buf)?))
             }
+        })
+    }
+
+    pub fn compress(self, nbt: Vec<u8>) -> Vec<u8> {
+        self.compress_with(nbt, &CompressionOptions::default())
+    }
+
+    /// Compresses NBT in this file type with the level and gzip header of `options`.
+    pub fn compress_with(self, nbt: Vec<u8>, options: &CompressionOptions) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            FileType::Raw => return nbt,
             FileType::GZip => {
-                let compressed_nbt = &nbt[..];
-                let mut d = GzDecoder::new(compressed_nbt);
-                d.read_to_end(&mut buf).unwrap();
+                let mut e = options.gz_builder().read(&nbt[..], options.compression());
+                e.read_to_end(&mut buf).unwrap();
+            }
+            FileType::Zlib => {
+                let mut e = ZlibEncoder::new(&nbt[..], options.compression());
+                e.read_to_end(&mut buf).unwrap();
             }
+            FileType::Lz4 => return lz4::compress(&nbt),
         }
         buf
-    };
-    let mut nbt = &nbt[..];
-    let _ = nbt.get_u8(); // 0x0a   - Tag Type: TAG_Compound
-    let _ = nbt.get_u16(); // 0x0000 - Name Length: 0
-    loop {
-        let tag_type = nbt.get_u8();
-        if walk(json, &mut nbt, tag_type, false) {
-            break;
-        }
     }
 }
 
-// Return: is_end
-fn walk(
-    json: &mut HashMap<String, Value>,
-    nbt: &mut &[u8],
-    tag_type: u8,
-    is_anonymous: bool,
-) -> bool {
-    match tag_type {
-        // TAG_End
-        0x00 => return true,
-        // TAG_Byte
-        0x01 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                format!(
-                    "{};B",
-                    cesu8::from_java_cesu8(get_name(nbt).as_ref()).unwrap()
-                )
-            };
-            let value = nbt.get_i8();
-            json.insert(name, Value::Number(Number::from(value)));
-        }
-        // TAG_Short
-        0x02 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                format!(
-                    "{};S",
-                    cesu8::from_java_cesu8(get_name(nbt).as_ref()).unwrap()
-                )
-            };
-            let value = nbt.get_i16();
-            json.insert(name, Value::Number(Number::from(value)));
-        }
-        // TAG_Int
-        0x03 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                cesu8::from_java_cesu8(get_name(nbt).as_ref())
-                    .unwrap()
-                    .to_string()
-            };
-            let value = nbt.get_i32();
-            json.insert(name, Value::Number(Number::from(value)));
+/// How gzip and zlib compressed NBT is written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CompressionOptions {
+    /// The level from 0 (stored) to 9 (smallest), where higher levels are clamped to 9
+    pub level: u32,
+    pub header: GzipHeader,
+}
+
+impl Default for CompressionOptions {
+    /// The fastest level with a zeroed header.
+    fn default() -> Self {
+        Self {
+            level: 1,
+            header: GzipHeader::default(),
         }
-        // TAG_Long
-        0x04 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                format!(
-                    "{};L",
-                    cesu8::from_java_cesu8(get_name(nbt).as_ref()).unwrap()
-                )
-            };
-            let value = nbt.get_i64();
-            let value = LongNumber::from(value);
-            match value {
-                LongNumber::Number(v) => {
-                    json.insert(name, Value::Number(Number::from(v)));
-                }
-                LongNumber::BigInt(v) => {
-                    json.insert(name, Value::String(v));
-                }
+    }
+}
+
+impl CompressionOptions {
+    pub(crate) fn compression(&self) -> Compression {
+        Compression::new(self.level.min(9))
+    }
+
+    pub(crate) fn gz_builder(&self) -> GzBuilder {
+        let (mtime, os) = match self.header {
+            GzipHeader::Zeroed => (0, UNKNOWN_OS),
+            GzipHeader::Current => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH);
+                (now.map_or(0, |v| v.as_secs() as u32), CURRENT_OS)
             }
+        };
+        GzBuilder::new().mtime(mtime).operating_system(os)
+    }
+}
+
+/// The modification time and operating system fields of the gzip header.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum GzipHeader {
+    /// No modification time and an unknown operating system, so that the same NBT is always
+    /// compressed into the same bytes
+    #[default]
+    Zeroed,
+    /// The current time and the operating system like the `gzip` command
+    Current,
+}
+
+// The operating system codes of RFC 1952
+#[cfg(unix)]
+const CURRENT_OS: u8 = 3;
+#[cfg(windows)]
+const CURRENT_OS: u8 = 11;
+#[cfg(not(any(unix, windows)))]
+const CURRENT_OS: u8 = UNKNOWN_OS;
+const UNKNOWN_OS: u8 = 255;
+
+pub fn get_file_type(nbt: &[u8]) -> Option<FileType> {
+    match nbt {
+        [0x01..=0x0c, ..] => Some(FileType::Raw),
+        [0x1f, 0x8b, ..] => Some(FileType::GZip),
+        [0x78, 0x01 | 0x5e | 0x9c | 0xda, ..] => Some(FileType::Zlib),
+        _ if nbt.starts_with(lz4::MAGIC) => Some(FileType::Lz4),
+        _ => None,
+    }
+}
+
+/// Byte order of the numbers in binary NBT.
+///
+/// Java Edition uses big-endian NBT, while Bedrock Edition uses little-endian NBT.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
+/// Whether NBT is stored in a file or sent over the network protocol.
+///
+/// Java Edition network NBT (since 1.20.2) omits the name of the root tag, while Bedrock Edition
+/// network NBT encodes ints, longs and lengths as (zigzag) varints and keeps the root name.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum NbtMode {
+    #[default]
+    File,
+    Network,
+}
+
+const BEDROCK_HEADER_LENGTH: usize = 8;
+
+/// Splits the 8-byte header of Bedrock Edition `level.dat` off, returning the storage version and
+/// the NBT following it.
+///
+/// The header consists of the little-endian storage version and the length of the NBT.
+pub fn split_bedrock_header(nbt: &[u8]) -> Option<(u32, &[u8])> {
+    if nbt.len() <= BEDROCK_HEADER_LENGTH {
+        return None;
+    }
+    let (mut header, body) = nbt.split_at(BEDROCK_HEADER_LENGTH);
+    let version = header.get_u32_le();
+    let len = header.get_u32_le() as usize;
+    if len != body.len() || body[0] != 0x0a {
+        return None;
+    }
+    Some((version, body))
+}
+
+/// Prepends the header of Bedrock Edition `level.dat` to little-endian NBT.
+pub fn put_bedrock_header(version: u32, nbt: Vec<u8>) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(BEDROCK_HEADER_LENGTH + nbt.len());
+    buf.put_u32_le(version);
+    buf.put_u32_le(nbt.len() as u32);
+    buf.extend(nbt);
+    buf
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NbtDecodeErrorKind {
+    UnexpectedEof,
+    UnknownTagId(u8),
+    InvalidCesu8,
+    NegativeArrayLength(i32),
+    InvalidVarInt,
+    UnknownFileType,
+    DecompressionFailed(io::ErrorKind),
+    Io(io::ErrorKind),
+}
+
+impl Display for NbtDecodeErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NbtDecodeErrorKind::UnexpectedEof => write!(f, "Unexpected end of file."),
+            NbtDecodeErrorKind::UnknownTagId(id) => write!(f, "Unknown tag id 0x{:02x}.", id),
+            NbtDecodeErrorKind::InvalidCesu8 => write!(f, "Invalid CESU-8 string."),
+            NbtDecodeErrorKind::NegativeArrayLength(len) => {
+                write!(f, "Negative array length {}.", len)
+            }
+            NbtDecodeErrorKind::InvalidVarInt => write!(f, "Invalid varint."),
+            NbtDecodeErrorKind::UnknownFileType => write!(f, "Unknown file type."),
+            NbtDecodeErrorKind::DecompressionFailed(kind) => {
+                write!(f, "Failed to decompress: {}.", kind)
+            }
+            NbtDecodeErrorKind::Io(kind) => write!(f, "Failed to read: {}.", kind),
         }
-        // TAG_Float
-        0x05 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                format!(
-                    "{};F",
-                    cesu8::from_java_cesu8(get_name(nbt).as_ref()).unwrap()
-                )
-            };
-            let value = nbt.get_f32();
-            json.insert(name, Value::Number(Number::from_f64(value as f64).unwrap()));
-        }
-        // TAG_Double
-        0x06 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                cesu8::from_java_cesu8(get_name(nbt).as_ref())
-                    .unwrap()
-                    .to_string()
-            };
-            let value = nbt.get_f64();
-            json.insert(name, Value::Number(Number::from_f64(value).unwrap()));
+    }
+}
+
+/// An error raised while decoding binary NBT.
+///
+/// `offset` is the byte offset into the decompressed NBT data and `path` is the tag path being
+/// decoded when the error occurred (e.g. `Data.Player.Pos[0]`), empty for the root tag.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NbtDecodeError {
+    pub offset: usize,
+    pub path: String,
+    pub kind: NbtDecodeErrorKind,
+}
+
+impl Display for NbtDecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (offset: {}, path: \"{}\")",
+            self.kind, self.offset, self.path
+        )
+    }
+}
+
+impl Error for NbtDecodeError {}
+
+/// An error raised by [`to_vec`] and [`from_slice`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum NbtSerdeError {
+    Message(String),
+    Decode(NbtDecodeError),
+    Encode(NbtEncodeError),
+}
+
+impl Display for NbtSerdeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NbtSerdeError::Message(msg) => write!(f, "{}", msg),
+            NbtSerdeError::Decode(err) => write!(f, "{}", err),
+            NbtSerdeError::Encode(err) => write!(f, "{}", err),
         }
-        // TAG_Byte_Array
-        0x07 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                format!(
-                    "{};B",
-                    cesu8::from_java_cesu8(get_name(nbt).as_ref()).unwrap()
-                )
-            };
-            let len = nbt.get_i32();
-            let mut buf = vec![];
-            for _ in 0..len {
-                buf.push(Value::Number(Number::from(nbt.get_i8())));
+    }
+}
+
+impl Error for NbtSerdeError {}
+
+impl serde::ser::Error for NbtSerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        NbtSerdeError::Message(msg.to_string())
+    }
+}
+
+impl serde::de::Error for NbtSerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        NbtSerdeError::Message(msg.to_string())
+    }
+}
+
+impl From<NbtDecodeError> for NbtSerdeError {
+    fn from(value: NbtDecodeError) -> Self {
+        NbtSerdeError::Decode(value)
+    }
+}
+
+impl From<NbtEncodeError> for NbtSerdeError {
+    fn from(value: NbtEncodeError) -> Self {
+        NbtSerdeError::Encode(value)
+    }
+}
+
+/// Reads NBT in any supported [`FileType`] into its root tag, keyed by the root name like in
+/// [`resolve_tag_types`].
+///
+/// The header of Bedrock Edition `level.dat` is skipped when reading little-endian NBT.
+pub fn read(
+    nbt: Vec<u8>,
+    endianness: Endianness,
+    mode: NbtMode,
+) -> Result<IndexMap<Cow<'static, [u8]>, TagType<'static>>, NbtDecodeError> {
+    let nbt = match endianness {
+        Endianness::Little => match split_bedrock_header(&nbt) {
+            Some((_, body)) => body.to_vec(),
+            None => nbt,
+        },
+        Endianness::Big => nbt,
+    };
+    let (_, decoder) = detect_decoder(&nbt[..])?;
+    let mut reader = NbtReader::new(decoder, endianness, mode);
+    reader.read_root()
+}
+
+/// Detects the [`FileType`] of NBT by its first bytes like [`get_file_type`], returning it with a
+/// reader of the decompressed NBT, see [`FileType::decoder`].
+///
+/// The header of Bedrock Edition `level.dat` is not skipped, as it is told by the length of the
+/// whole file.
+pub fn detect_decoder<'a, R: Read + 'a>(
+    mut nbt: R,
+) -> Result<(FileType, Box<dyn Read + 'a>), NbtDecodeError> {
+    let error = |kind| NbtDecodeError {
+        offset: 0,
+        path: "".to_string(),
+        kind,
+    };
+    let mut head = vec![];
+    nbt.by_ref()
+        .take(lz4::MAGIC.len() as u64)
+        .read_to_end(&mut head)
+        .map_err(|err| error(NbtDecodeErrorKind::Io(err.kind())))?;
+    let file_type = get_file_type(&head).ok_or(error(NbtDecodeErrorKind::UnknownFileType))?;
+    let nbt = file_type
+        .decoder(Read::chain(io::Cursor::new(head), nbt))
+        .map_err(|err| error(NbtDecodeErrorKind::DecompressionFailed(err.kind())))?;
+    Ok((file_type, nbt))
+}
+
+/// Converts NBT into a JSON document holding the root tag, see [`read`] and
+/// [`resolve_tag_types`].
+pub fn to_json(
+    nbt: Vec<u8>,
+    json: &mut Map<String, Value>,
+    endianness: Endianness,
+    mode: NbtMode,
+) -> Result<(), NbtDecodeError> {
+    let nbt = read(nbt, endianness, mode)?;
+    tag_types_to_json(&nbt, json);
+    Ok(())
+}
+
+/// Decodes uncompressed NBT into its root tag, keyed by the root name like in
+/// [`resolve_tag_types`].
+pub fn decode(
+    nbt: &[u8],
+    endianness: Endianness,
+    mode: NbtMode,
+) -> Result<IndexMap<Cow<'static, [u8]>, TagType<'static>>, NbtDecodeError> {
+    NbtReader::new(nbt, endianness, mode).read_root()
+}
+
+/// Converts a root tag into a JSON document, the reverse of [`resolve_tag_types`].
+pub fn tag_types_to_json(nbt: &IndexMap<Cow<[u8]>, TagType>, json: &mut Map<String, Value>) {
+    tag_types_to_json_as(nbt, json, JsonFormat::Compact);
+}
+
+/// Converts a root tag into a JSON document in the given [`JsonFormat`].
+pub fn tag_types_to_json_as(
+    nbt: &IndexMap<Cow<[u8]>, TagType>,
+    json: &mut Map<String, Value>,
+    format: JsonFormat,
+) {
+    json.insert(
+        VERSION_KEY.to_string(),
+        Value::Number(Number::from(JSON_FORMAT_VERSION)),
+    );
+    match format {
+        JsonFormat::Compact => {
+            let mut root = Map::new();
+            for (name, value) in nbt.iter() {
+                walk(&mut root, Some(name), value);
+            }
+            for (key, value) in root {
+                json.insert(escape_root_key(key), value);
             }
-            json.insert(name, Value::Array(buf));
         }
-        // TAG_String
-        0x08 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                cesu8::from_java_cesu8(get_name(nbt).as_ref())
-                    .unwrap()
-                    .to_string()
-            };
-            let len = nbt.get_u16();
-            let mut buf = vec![];
-            for _ in 0..len {
-                buf.push(nbt.get_u8());
+        JsonFormat::Typed => {
+            json.insert(FORMAT_KEY.to_string(), Value::String("typed".to_string()));
+            for (name, value) in nbt.iter() {
+                json.insert(
+                    escape_root_key(cesu8_to_string(name)),
+                    typed::to_json(value),
+                );
             }
-            let string = cesu8::from_java_cesu8(buf.as_ref()).unwrap().to_string();
-            json.insert(name, Value::String(string));
         }
-        // TAG_List
-        0x09 => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                cesu8::from_java_cesu8(get_name(nbt).as_ref())
-                    .unwrap()
-                    .to_string()
-            };
-            let item_type = nbt.get_u8();
-            let name = if is_anonymous {
-                name
-            } else {
-                let tag_type = TagType::u8_to_str(item_type).expect("Unknown tag type.");
-                format!("{};{}", name, tag_type)
-            };
-            let len = nbt.get_i32();
-            let mut buf = vec![];
-            for _ in 0..len {
-                let mut buf1 = HashMap::new();
-                walk(&mut buf1, nbt, item_type, true);
-                buf.extend(buf1.into_values());
+    }
+}
+
+// A $ starting the key of the root tag is doubled, so that it is not taken for the metadata like
+// $version.
+fn escape_root_key(key: String) -> String {
+    if key.starts_with('$') {
+        format!("${}", key)
+    } else {
+        key
+    }
+}
+
+// Non-finite floating point values are written as strings: NaN, Infinity, -Infinity, or
+// NaN:0x<bits> for NaNs other than the one of Rust, so that their bits are kept.
+macro_rules! non_finite_float {
+    ($to_json:ident, $from_str:ident, $t:ty, $bits:ty) => {
+        fn $to_json(v: $t) -> Value {
+            if v.is_finite() {
+                // The shortest decimal representation of the value, which is exact in f64 too
+                let v = f64::from_str(&v.to_string()).unwrap();
+                return Value::Number(Number::from_f64(v).unwrap());
             }
-            json.insert(name, Value::Array(buf));
+            Value::String(match v {
+                v if v.to_bits() == <$t>::NAN.to_bits() => "NaN".to_string(),
+                v if v.is_nan() => format!("NaN:{:#x}", v.to_bits()),
+                v if v.is_sign_positive() => "Infinity".to_string(),
+                _ => "-Infinity".to_string(),
+            })
         }
-        // TAG_Compound
-        0x0a => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                cesu8::from_java_cesu8(get_name(nbt).as_ref())
-                    .unwrap()
-                    .to_string()
-            };
-            let mut buf = HashMap::new();
-            loop {
-                let tag_type = nbt.get_u8();
-                if walk(&mut buf, nbt, tag_type, false) {
-                    break;
+
+        pub(crate) fn $from_str(v: &str) -> Option<$t> {
+            match v {
+                "NaN" => Some(<$t>::NAN),
+                "Infinity" => Some(<$t>::INFINITY),
+                "-Infinity" => Some(<$t>::NEG_INFINITY),
+                v => {
+                    let bits = <$bits>::from_str_radix(v.strip_prefix("NaN:0x")?, 16).ok()?;
+                    Some(<$t>::from_bits(bits)).filter(|v| v.is_nan())
                 }
             }
-            json.insert(name, Value::Object(Map::from_iter(buf)));
         }
-        // TAG_Int_Array
-        0x0b => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                format!(
-                    "{};I",
-                    cesu8::from_java_cesu8(get_name(nbt).as_ref()).unwrap()
-                )
-            };
-            let len = nbt.get_i32();
+    };
+}
+
+non_finite_float!(f32_to_json, f32_from_non_finite_str, f32, u32);
+non_finite_float!(f64_to_json, f64_from_non_finite_str, f64, u64);
+
+fn long_to_json(v: &LongNumber) -> Value {
+    match v {
+        LongNumber::Number(v) => Value::Number(Number::from(*v)),
+        LongNumber::BigInt(v) => Value::String(v.clone()),
+    }
+}
+
+// The suffix of a list, which also names the item types of nested lists, e.g. TAG_List;TAG_Int
+//
+// When nested lists differ in their suffixes, e.g. an empty TAG_End list next to a TAG_Short
+// list, the suffix ends with * instead and each of them is written as an object holding it under
+// its own suffix, e.g. {";TAG_End": []}.
+fn list_suffix(item_type: u8, items: &[TagType]) -> String {
+    // The item type has been validated on decoding or resolving.
+    let name = TagType::u8_to_str(item_type).unwrap_or("TAG_End");
+    if item_type != 0x09 {
+        return name.to_string();
+    }
+    let mut nested = items.iter().map(|v| match v {
+        TagType::List(item_type, v) => list_suffix(*item_type, v),
+        _ => "TAG_End".to_string(),
+    });
+    match nested.next() {
+        Some(first) if nested.all(|v| v == first) => format!("{};{}", name, first),
+        Some(_) => format!("{};*", name),
+        None => format!("{};TAG_End", name),
+    }
+}
+
+// name: None for list items
+fn walk(json: &mut Map<String, Value>, name: Option<&[u8]>, tag: &TagType) {
+    let list_suffix_buf;
+    let (suffix, value) = match tag {
+        TagType::End => return,
+        TagType::Byte(v) => (Some("B"), Value::Number(Number::from(*v))),
+        TagType::Short(v) => (Some("S"), Value::Number(Number::from(*v))),
+        TagType::Int(v) => (None, Value::Number(Number::from(*v))),
+        TagType::Long(v) => (Some("L"), long_to_json(v)),
+        TagType::Float(v) => (Some("F"), f32_to_json(*v)),
+        // Suffixed only when non-finite, as a string would be read as TAG_String otherwise
+        TagType::Double(v) if !v.is_finite() => (Some("D"), f64_to_json(*v)),
+        TagType::Double(v) => (None, f64_to_json(*v)),
+        TagType::ByteArray(v) => (
+            Some("B"),
+            Value::Array(v.iter().map(|v| Value::Number(Number::from(*v))).collect()),
+        ),
+        TagType::String(v) => (None, Value::String(cesu8_to_string(v))),
+        TagType::List(item_type, v) => {
+            list_suffix_buf = list_suffix(*item_type, v);
+            let is_self_described = list_suffix_buf.ends_with(";*");
             let mut buf = vec![];
-            for _ in 0..len {
-                buf.push(Value::Number(Number::from(nbt.get_i32())));
+            for v in v.iter() {
+                let mut buf1 = Map::new();
+                if is_self_described {
+                    walk(&mut buf1, Some(b""), v);
+                    buf.push(Value::Object(buf1));
+                } else {
+                    walk(&mut buf1, None, v);
+                    buf.extend(buf1.into_iter().map(|(_, v)| v));
+                }
             }
-            json.insert(name, Value::Array(buf));
+            (Some(list_suffix_buf.as_str()), Value::Array(buf))
         }
-        // TAG_Long_Array
-        0x0c => {
-            let name = if is_anonymous {
-                "".to_string()
-            } else {
-                format!(
-                    "{};L",
-                    cesu8::from_java_cesu8(get_name(nbt).as_ref()).unwrap()
-                )
-            };
-            let len = nbt.get_i32();
-            let mut buf = vec![];
-            for _ in 0..len {
-                let v = LongNumber::from(nbt.get_i64());
-                match v {
-                    LongNumber::Number(v) => {
-                        buf.push(Value::Number(Number::from(v)));
-                    }
-                    LongNumber::BigInt(v) => {
-                        buf.push(Value::String(v));
-                    }
-                }
+        TagType::Compound(v) => {
+            let mut buf = Map::new();
+            for (name, v) in v.iter() {
+                walk(&mut buf, Some(name), v);
+            }
+            (None, Value::Object(buf))
+        }
+        TagType::IntArray(v) => (
+            Some("I"),
+            Value::Array(v.iter().map(|v| Value::Number(Number::from(*v))).collect()),
+        ),
+        TagType::LongArray(v) => (
+            Some("L"),
+            Value::Array(v.iter().map(long_to_json).collect()),
+        ),
+    };
+    let name = match name {
+        Some(name) => {
+            let name = escape_name(cesu8_to_string(name));
+            match suffix {
+                Some(suffix) => format!("{};{}", name, suffix),
+                None => name,
             }
-            json.insert(name, Value::Array(buf));
         }
-        _ => unreachable!(),
+        None => "".to_string(),
+    };
+    json.insert(name, value);
+}
+
+// Tag names are separated from the type suffix by ;, so that ; in names is doubled, e.g. the
+// TAG_Byte named a;b is keyed a;;b;B.
+fn escape_name(name: String) -> String {
+    match name.contains(';') {
+        true => name.replace(';', ";;"),
+        false => name,
+    }
+}
+
+/// Splits a key into the tag name and the type suffix, which follows the first ; not doubled.
+pub(crate) fn split_key(key: &str) -> (Cow<'_, str>, Option<&str>) {
+    let mut i = 0;
+    while let Some(j) = key[i..].find(';') {
+        i += j;
+        if !key[i + 1..].starts_with(';') {
+            return (unescape_name(&key[..i]), Some(&key[i + 1..]));
+        }
+        i += 2;
+    }
+    (unescape_name(key), None)
+}
+
+fn unescape_name(name: &str) -> Cow<'_, str> {
+    match name.contains(";;") {
+        true => Cow::Owned(name.replace(";;", ";")),
+        false => Cow::Borrowed(name),
     }
-    false
 }
 
-fn get_name(nbt: &mut &[u8]) -> String {
-    let name_len = nbt.get_u16();
-    let mut name: Vec<u8> = vec![];
-    for _ in 0..name_len {
-        name.push(nbt.get_u8());
+pub(crate) fn cesu8_to_string(v: &[u8]) -> String {
+    match cesu8::from_java_cesu8(v) {
+        Ok(v) => v.into_owned(),
+        Err(_) => String::from_utf8_lossy(v).into_owned(),
     }
-    String::from_utf8(name).unwrap()
 }
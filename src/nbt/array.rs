@@ -0,0 +1,90 @@
+//! Wrapper types picking the array tags instead of TAG_List in [`to_vec`](super::to_vec) and
+//! [`from_slice`](super::from_slice).
+//!
+//! Other serializers see them as plain sequences.
+
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::fmt;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+
+// The newtype struct names recognized by the NBT serializer and deserializer
+pub(crate) const BYTE_ARRAY_NAME: &str = "$nbt-json::ByteArray";
+pub(crate) const INT_ARRAY_NAME: &str = "$nbt-json::IntArray";
+pub(crate) const LONG_ARRAY_NAME: &str = "$nbt-json::LongArray";
+
+/// A TAG_Byte_Array.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ByteArray(pub Vec<i8>);
+
+/// A TAG_Int_Array.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct IntArray(pub Vec<i32>);
+
+/// A TAG_Long_Array.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LongArray(pub Vec<i64>);
+
+struct ArrayVisitor<T, A> {
+    expecting: &'static str,
+    wrap: fn(Vec<T>) -> A,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>, A> Visitor<'de> for ArrayVisitor<T, A> {
+    type Value = A;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, d: D) -> Result<A, D::Error> {
+        Vec::deserialize(d).map(self.wrap)
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<A, S::Error> {
+        let mut buf = vec![];
+        while let Some(v) = seq.next_element()? {
+            buf.push(v);
+        }
+        Ok((self.wrap)(buf))
+    }
+}
+
+macro_rules! array {
+    ($t:ident, $item:ty, $name:expr, $expecting:expr) => {
+        impl Serialize for $t {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.serialize_newtype_struct($name, &self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                d.deserialize_newtype_struct(
+                    $name,
+                    ArrayVisitor::<$item, _> {
+                        expecting: $expecting,
+                        wrap: $t,
+                        marker: PhantomData,
+                    },
+                )
+            }
+        }
+
+        impl From<Vec<$item>> for $t {
+            fn from(v: Vec<$item>) -> Self {
+                $t(v)
+            }
+        }
+    };
+}
+
+array!(ByteArray, i8, BYTE_ARRAY_NAME, "a byte array");
+array!(IntArray, i32, INT_ARRAY_NAME, "an int array");
+array!(LongArray, i64, LONG_ARRAY_NAME, "a long array");
@@ -0,0 +1,270 @@
+use super::cesu8_to_string;
+use super::read;
+use super::Endianness;
+use super::NbtMode;
+use super::NbtSerdeError;
+use super::TagType;
+use serde::de;
+use serde::de::DeserializeOwned;
+use serde::de::DeserializeSeed;
+use serde::de::IntoDeserializer;
+use serde::de::Visitor;
+use serde::forward_to_deserialize_any;
+use std::borrow::Cow;
+use std::vec;
+
+/// Deserializes a value from big-endian NBT in any supported [`FileType`](super::FileType).
+///
+/// The name of the root tag is ignored. See [`to_vec`](super::to_vec) for the mapping of types.
+pub fn from_slice<T: DeserializeOwned>(nbt: &[u8]) -> Result<T, NbtSerdeError> {
+    let root = read(nbt.to_vec(), Endianness::Big, NbtMode::File)?;
+    match root.into_iter().next() {
+        Some((_, tag)) => from_tag(tag),
+        None => Err(de::Error::custom("The root tag is empty.")),
+    }
+}
+
+/// Deserializes a value from a tag, see [`from_slice`].
+pub fn from_tag<T: DeserializeOwned>(tag: TagType<'static>) -> Result<T, NbtSerdeError> {
+    T::deserialize(TagDeserializer(tag))
+}
+
+struct TagDeserializer(TagType<'static>);
+
+impl<'de> de::Deserializer<'de> for TagDeserializer {
+    type Error = NbtSerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::End => visitor.visit_unit(),
+            TagType::Byte(v) => visitor.visit_i8(v),
+            TagType::Short(v) => visitor.visit_i16(v),
+            TagType::Int(v) => visitor.visit_i32(v),
+            TagType::Long(v) => visitor.visit_i64(v.into()),
+            TagType::Float(v) => visitor.visit_f32(v),
+            TagType::Double(v) => visitor.visit_f64(v),
+            TagType::String(v) => visitor.visit_string(cesu8_to_string(&v)),
+            TagType::ByteArray(v) => visitor.visit_seq(SeqDeserializer::new(v, TagType::Byte)),
+            TagType::IntArray(v) => visitor.visit_seq(SeqDeserializer::new(v, TagType::Int)),
+            TagType::LongArray(v) => visitor.visit_seq(SeqDeserializer::new(v, TagType::Long)),
+            TagType::List(_, v) => visitor.visit_seq(SeqDeserializer::new(v, |v| v)),
+            TagType::Compound(v) => visitor.visit_map(MapDeserializer {
+                entries: v.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::Byte(v) => visitor.visit_bool(v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // Unsigned integers are read back from the bits of the signed tag of the same size.
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::Byte(v) => visitor.visit_u8(v as u8),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::Short(v) => visitor.visit_u16(v as u16),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::Int(v) => visitor.visit_u32(v as u32),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::Long(v) => visitor.visit_u64(i64::from(v) as u64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::End => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::End => visitor.visit_unit(),
+            TagType::Compound(v) if v.is_empty() => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            TagType::String(v) => visitor.visit_enum(cesu8_to_string(&v).into_deserializer()),
+            TagType::Compound(v) if v.len() == 1 => {
+                let (variant, value) = v.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer {
+                    variant: cesu8_to_string(&variant),
+                    value,
+                })
+            }
+            _ => Err(de::Error::custom(
+                "An enum requires a string or a compound with a single entry.",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string bytes byte_buf seq tuple tuple_struct
+        map struct identifier ignored_any
+    }
+}
+
+struct SeqDeserializer<T> {
+    items: vec::IntoIter<T>,
+    wrap: fn(T) -> TagType<'static>,
+}
+
+impl<T> SeqDeserializer<T> {
+    fn new(items: Vec<T>, wrap: fn(T) -> TagType<'static>) -> Self {
+        Self {
+            items: items.into_iter(),
+            wrap,
+        }
+    }
+}
+
+impl<'de, T> de::SeqAccess<'de> for SeqDeserializer<T> {
+    type Error = NbtSerdeError;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        match self.items.next() {
+            Some(v) => seed.deserialize(TagDeserializer((self.wrap)(v))).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapDeserializer {
+    entries: indexmap::map::IntoIter<Cow<'static, [u8]>, TagType<'static>>,
+    value: Option<TagType<'static>>,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+    type Error = NbtSerdeError;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        match self.entries.next() {
+            Some((k, v)) => {
+                self.value = Some(v);
+                let k: de::value::StringDeserializer<NbtSerdeError> =
+                    cesu8_to_string(&k).into_deserializer();
+                seed.deserialize(k).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        match self.value.take() {
+            Some(v) => seed.deserialize(TagDeserializer(v)),
+            None => Err(de::Error::custom("A map value without key.")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: TagType<'static>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = NbtSerdeError;
+    type Variant = TagDeserializer;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self::Variant), Self::Error> {
+        let variant: de::value::StringDeserializer<NbtSerdeError> =
+            self.variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, TagDeserializer(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for TagDeserializer {
+    type Error = NbtSerdeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
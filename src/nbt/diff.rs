@@ -0,0 +1,81 @@
+//! Differences between two tags, by tag path.
+
+use super::path;
+use super::path::PathSegment;
+use super::NbtValue;
+
+/// A difference between two tags, found by [`NbtValue::diff`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagDiff {
+    pub path: String,
+    pub kind: TagDiffKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagDiffKind {
+    /// A tag only found in the other tree
+    Added(NbtValue),
+    /// A tag only found in this tree
+    Removed(NbtValue),
+    /// A tag of another value or type, followed by the one of the other tree
+    Changed(NbtValue, NbtValue),
+}
+
+impl NbtValue {
+    /// Compares the tag with another, listing the tags added, removed and changed below it.
+    ///
+    /// Entries of compounds are matched by name and items of lists by index. Floats are equal if
+    /// their bits are, so that NaNs are compared too.
+    pub fn diff(&self, other: &NbtValue) -> Vec<TagDiff> {
+        let mut diffs = vec![];
+        diff_values(self, other, &mut vec![], &mut diffs);
+        diffs
+    }
+}
+
+fn diff_values(a: &NbtValue, b: &NbtValue, path: &mut Vec<PathSegment>, diffs: &mut Vec<TagDiff>) {
+    match (a, b) {
+        (NbtValue::Compound(a), NbtValue::Compound(b)) => {
+            for (k, v) in a.iter() {
+                path.push(PathSegment::Name(k.clone()));
+                match b.get(k) {
+                    Some(w) => diff_values(v, w, path, diffs),
+                    None => push(diffs, path, TagDiffKind::Removed(v.clone())),
+                }
+                path.pop();
+            }
+            for (k, v) in b.iter().filter(|(k, _)| !a.contains_key(k)) {
+                path.push(PathSegment::Name(k.clone()));
+                push(diffs, path, TagDiffKind::Added(v.clone()));
+                path.pop();
+            }
+        }
+        // Lists of other item types are changed as a whole, unless one of them is empty.
+        (NbtValue::List(s, a), NbtValue::List(t, b)) if s == t || a.is_empty() || b.is_empty() => {
+            for i in 0..a.len().max(b.len()) {
+                path.push(PathSegment::Index(i));
+                match (a.get(i), b.get(i)) {
+                    (Some(v), Some(w)) => diff_values(v, w, path, diffs),
+                    (Some(v), None) => push(diffs, path, TagDiffKind::Removed(v.clone())),
+                    (None, Some(w)) => push(diffs, path, TagDiffKind::Added(w.clone())),
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        (NbtValue::Float(v), NbtValue::Float(w)) if v.to_bits() == w.to_bits() => {}
+        (NbtValue::Double(v), NbtValue::Double(w)) if v.to_bits() == w.to_bits() => {}
+        (NbtValue::Float(_) | NbtValue::Double(_), _) => {
+            push(diffs, path, TagDiffKind::Changed(a.clone(), b.clone()));
+        }
+        _ if a != b => push(diffs, path, TagDiffKind::Changed(a.clone(), b.clone())),
+        _ => {}
+    }
+}
+
+fn push(diffs: &mut Vec<TagDiff>, path: &[PathSegment], kind: TagDiffKind) {
+    diffs.push(TagDiff {
+        path: path::to_string(path),
+        kind,
+    });
+}
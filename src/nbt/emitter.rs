@@ -0,0 +1,227 @@
+//! A writer of JSON documents driven by the events of [`NbtReader`], so that NBT is converted
+//! as it is read.
+
+use super::escape_name;
+use super::escape_root_key;
+use super::file_type_name;
+use super::list_suffix;
+use super::reader::TagBuilder;
+use super::walk;
+use super::FileType;
+use super::NbtDecodeError;
+use super::NbtEvent;
+use super::NbtReader;
+use super::TagType;
+use super::COMPRESSION_KEY;
+use super::JSON_FORMAT_VERSION;
+use super::VERSION_KEY;
+use crate::json;
+use crate::json::JsonStyle;
+use serde_json::Map;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+
+/// A writer of the compact JSON document of NBT, fed with the events of [`NbtReader`].
+///
+/// Compounds and lists of compounds are written as their events come, while other lists are held
+/// in memory until read whole. The suffix of a list of lists names the item types of all the
+/// nested lists, so that such a list is held with everything in it, even compounds, e.g. a
+/// `TAG_List` of `TAG_List`s of `TAG_Compound`s. Memory is thus bounded by the largest of these
+/// lists rather than by the whole document.
+///
+/// The document is the same as written by [`tag_types_to_json`](super::tag_types_to_json),
+/// [`set_compression`](super::set_compression) and [`json::to_string`].
+pub struct JsonEmitter<W> {
+    out: W,
+    style: JsonStyle,
+    // The closing brackets of the open objects and arrays with the numbers of their entries,
+    // starting with the document
+    open: Vec<(u8, usize)>,
+    // The list other than of compounds being read before it is written
+    builder: TagBuilder,
+}
+
+impl<W: Write> JsonEmitter<W> {
+    /// Starts a document, recording the compression of the source NBT if given.
+    pub fn new(out: W, compression: Option<FileType>, style: JsonStyle) -> io::Result<Self> {
+        let mut emitter = Self {
+            out,
+            style,
+            open: vec![],
+            builder: TagBuilder::default(),
+        };
+        emitter.start(None, b'{', b'}')?;
+        let version = Value::from(JSON_FORMAT_VERSION);
+        emitter.write_entry(Some(VERSION_KEY.to_string()), &version)?;
+        if let Some(file_type) = compression {
+            let file_type = Value::String(file_type_name(file_type));
+            emitter.write_entry(Some(COMPRESSION_KEY.to_string()), &file_type)?;
+        }
+        Ok(emitter)
+    }
+
+    /// Writes the JSON of an event.
+    pub fn write_event(&mut self, event: NbtEvent) -> io::Result<()> {
+        let is_read_whole = matches!(
+            event,
+            NbtEvent::ListStart { item_type, .. } if item_type != 0x0a
+        );
+        if is_read_whole || self.builder.is_building() {
+            return match self.builder.push(event) {
+                Some((name, tag)) => self.write_tag(name, &tag),
+                None => Ok(()),
+            };
+        }
+        match event {
+            NbtEvent::CompoundStart { name } => {
+                let key = name.map(|v| self.key(v, None));
+                self.start(key, b'{', b'}')
+            }
+            NbtEvent::ListStart { name, .. } => {
+                let key = name.map(|v| self.key(v, Some(&list_suffix(0x0a, &[]))));
+                self.start(key, b'[', b']')
+            }
+            NbtEvent::Value { name, value } => self.write_tag(name, &value),
+            NbtEvent::End => self.end(),
+        }
+    }
+
+    /// Ends the document after the events of the root tag, returning the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end()?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    // The key of a compound or a list of compounds, escaped like by walk
+    fn key(&self, name: String, suffix: Option<&str>) -> String {
+        let name = escape_name(name);
+        self.escape_key(match suffix {
+            Some(suffix) => format!("{};{}", name, suffix),
+            None => name,
+        })
+    }
+
+    fn escape_key(&self, key: String) -> String {
+        match self.open.len() {
+            1 => escape_root_key(key),
+            _ => key,
+        }
+    }
+
+    // Writes a tag as a whole. name: None for list items
+    fn write_tag(&mut self, name: Option<String>, tag: &TagType) -> io::Result<()> {
+        let name = name.map(|v| cesu8::to_java_cesu8(&v).into_owned());
+        let mut json = Map::new();
+        walk(&mut json, name.as_deref(), tag);
+        // Nothing is written for TAG_End.
+        let Some((key, value)) = json.into_iter().next() else {
+            return Ok(());
+        };
+        let key = name.map(|_| self.escape_key(key));
+        self.write_entry(key, &value)
+    }
+
+    fn write_entry(&mut self, key: Option<String>, value: &Value) -> io::Result<()> {
+        self.put_key(key)?;
+        let mut buf = String::new();
+        json::write_value(&mut buf, value, &self.style, self.open.len());
+        self.out.write_all(buf.as_bytes())
+    }
+
+    // Opens an object or an array
+    fn start(&mut self, key: Option<String>, bracket: u8, closing: u8) -> io::Result<()> {
+        if !self.open.is_empty() {
+            self.put_key(key)?;
+        }
+        self.open.push((closing, 0));
+        self.out.write_all(&[bracket])
+    }
+
+    // Closes the innermost object or array
+    fn end(&mut self) -> io::Result<()> {
+        // Every End has a matching start.
+        let (closing, entries) = self.open.pop().unwrap();
+        let mut buf = String::new();
+        if let Some(indent) = self.style.indent.filter(|_| entries > 0) {
+            json::put_indent(&mut buf, indent, self.open.len());
+        }
+        buf.push(closing as char);
+        self.out.write_all(buf.as_bytes())
+    }
+
+    // Separates an entry from the previous one of the innermost object or array
+    fn put_key(&mut self, key: Option<String>) -> io::Result<()> {
+        let depth = self.open.len();
+        let mut buf = String::new();
+        // The document is open.
+        let (_, entries) = self.open.last_mut().unwrap();
+        if *entries > 0 {
+            buf.push(',');
+        }
+        *entries += 1;
+        if let Some(indent) = self.style.indent {
+            json::put_indent(&mut buf, indent, depth);
+        }
+        if let Some(key) = key {
+            buf.push_str(&Value::String(key).to_string());
+            buf.push_str(match self.style.indent {
+                Some(_) => ": ",
+                None => ":",
+            });
+        }
+        self.out.write_all(buf.as_bytes())
+    }
+}
+
+/// An error raised by [`write_json`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum JsonEmitError {
+    Decode(NbtDecodeError),
+    Io(io::ErrorKind),
+}
+
+impl Display for JsonEmitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonEmitError::Decode(err) => write!(f, "{}", err),
+            JsonEmitError::Io(kind) => write!(f, "Failed to write: {}.", kind),
+        }
+    }
+}
+
+impl Error for JsonEmitError {}
+
+impl From<NbtDecodeError> for JsonEmitError {
+    fn from(value: NbtDecodeError) -> Self {
+        JsonEmitError::Decode(value)
+    }
+}
+
+impl From<io::Error> for JsonEmitError {
+    fn from(value: io::Error) -> Self {
+        JsonEmitError::Io(value.kind())
+    }
+}
+
+/// Writes the JSON document of the NBT read by `nbt` to `out` as it is read, see [`JsonEmitter`].
+///
+/// `out` is written in small pieces, so that it is better buffered.
+pub fn write_json<R: Read, W: Write>(
+    nbt: &mut NbtReader<R>,
+    out: W,
+    compression: Option<FileType>,
+    style: JsonStyle,
+) -> Result<W, JsonEmitError> {
+    let mut emitter = JsonEmitter::new(out, compression, style)?;
+    while let Some(event) = nbt.next_event()? {
+        emitter.write_event(event)?;
+    }
+    Ok(emitter.finish()?)
+}
@@ -0,0 +1,493 @@
+//! Type hints for hand-written JSON, naming the tag types of entries without type suffixes, and
+//! validating tags against them.
+
+use super::cesu8_to_string;
+use super::path;
+use super::path::PathSegment;
+use super::split_key;
+use super::typed;
+use super::TagType;
+use super::FORMAT_KEY;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TypeHintsError {
+    InvalidFile(String),
+    InvalidPath(String),
+    UnknownType(String),
+}
+
+impl Display for TypeHintsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeHintsError::InvalidFile(reason) => write!(f, "Invalid type hints: {}.", reason),
+            TypeHintsError::InvalidPath(path) => write!(f, "Invalid tag path \"{}\".", path),
+            TypeHintsError::UnknownType(name) => write!(f, "Unknown tag type \"{}\".", name),
+        }
+    }
+}
+
+impl Error for TypeHintsError {}
+
+/// A tag not matching the [`TypeHints`], found by [`TypeHints::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub kind: SchemaViolationKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaViolationKind {
+    /// A tag without hint in a compound listing all of its entries
+    UnknownTag,
+    /// A required tag missing from its compound
+    MissingTag,
+    MismatchedType {
+        expected: Vec<u8>,
+        actual: u8,
+    },
+    /// A number out of the range of its hint
+    OutOfRange {
+        value: f64,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            SchemaViolationKind::UnknownTag => write!(f, "Unknown tag \"{}\".", self.path),
+            SchemaViolationKind::MissingTag => write!(f, "Missing tag \"{}\".", self.path),
+            SchemaViolationKind::MismatchedType { expected, actual } => write!(
+                f,
+                "Tag \"{}\" is {}, but {} is expected.",
+                self.path,
+                TagType::u8_to_str(*actual).unwrap_or("?"),
+                expected
+                    .iter()
+                    .map(|v| TagType::u8_to_str(*v).unwrap_or("?"))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ),
+            SchemaViolationKind::OutOfRange { value, min, max } => {
+                write!(f, "Tag \"{}\" is {}, but ", self.path, value)?;
+                match (min, max) {
+                    (Some(min), Some(max)) => write!(f, "{} to {} is expected.", min, max),
+                    (Some(min), None) => write!(f, "at least {} is expected.", min),
+                    (None, Some(max)) => write!(f, "at most {} is expected.", max),
+                    (None, None) => write!(f, "any number is expected."),
+                }
+            }
+        }
+    }
+}
+
+// A hint as written in files, either only a type or a table like
+// `{ type = "byte", required = true, min = 0, max = 3 }`
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum HintSource {
+    Type(String),
+    Table(HintTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct HintTable {
+    // One type or several allowed ones, the first of which is used for suffixes
+    #[serde(rename = "type")]
+    types: TypeNames,
+    #[serde(default)]
+    required: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+    #[serde(default)]
+    closed: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TypeNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Hint {
+    types: Vec<u8>,
+    required: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+/// Tag types by tag path, consulted by [`TypeHints::apply`] for entries without type suffixes.
+///
+/// Paths start below the root tag and use `[]` for the items of lists, e.g. `Inventory[].Count`
+/// or `Rotation[]`. Types are named like in the typed format (`byte`, `float`, `list`, ...) or
+/// like `TAG_Byte`.
+///
+/// Hints also serve as schemas for [`TypeHints::validate`], like the bundled
+/// [`Schema`](super::Schema)s. For this, a hint may be a table instead, with the allowed `type`
+/// or types, whether the tag is `required`, the `min` and `max` of numbers and whether a compound
+/// is `closed`, i.e. lists all of its entries.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TypeHints {
+    hints: HashMap<String, Hint>,
+    // The paths of compounds whose entries all have hints
+    closed: HashSet<String>,
+}
+
+impl TypeHints {
+    /// Reads hints from a JSON object mapping paths to types or tables.
+    pub fn from_json(hints: &str) -> Result<Self, TypeHintsError> {
+        let hints: HashMap<String, HintSource> = serde_json::from_str(hints)
+            .map_err(|err| TypeHintsError::InvalidFile(err.to_string()))?;
+        Self::from_map(hints)
+    }
+
+    /// Reads hints from a TOML table mapping paths to types or tables, e.g.
+    /// `"Inventory[].Count" = "byte"`.
+    pub fn from_toml(hints: &str) -> Result<Self, TypeHintsError> {
+        let hints: HashMap<String, HintSource> =
+            toml::from_str(hints).map_err(|err| TypeHintsError::InvalidFile(err.to_string()))?;
+        Self::from_map(hints)
+    }
+
+    pub(crate) fn from_map(hints: HashMap<String, HintSource>) -> Result<Self, TypeHintsError> {
+        let mut this = Self {
+            hints: HashMap::with_capacity(hints.len()),
+            closed: HashSet::new(),
+        };
+        for (k, v) in hints {
+            let path = normalize_path(&k).filter(|v| !v.is_empty());
+            let path = path.ok_or(TypeHintsError::InvalidPath(k))?;
+            let table = match v {
+                HintSource::Type(v) => HintTable {
+                    types: TypeNames::One(v),
+                    required: false,
+                    min: None,
+                    max: None,
+                    closed: false,
+                },
+                HintSource::Table(v) => v,
+            };
+            let types = match table.types {
+                TypeNames::One(v) => vec![v],
+                TypeNames::Many(v) => v,
+            };
+            if types.is_empty() {
+                return Err(TypeHintsError::InvalidFile(format!(
+                    "no type for \"{}\"",
+                    path
+                )));
+            }
+            let types = types
+                .into_iter()
+                .map(|v| {
+                    typed::type_id(&v)
+                        .or(TagType::str_to_u8(&v).ok())
+                        .filter(|v| *v != 0x00)
+                        .ok_or(TypeHintsError::UnknownType(v))
+                })
+                .collect::<Result<_, _>>()?;
+            if table.closed {
+                this.closed.insert(path.clone());
+            }
+            this.hints.insert(
+                path,
+                Hint {
+                    types,
+                    required: table.required,
+                    min: table.min,
+                    max: table.max,
+                },
+            );
+        }
+        Ok(this)
+    }
+
+    /// Marks a compound as listing all of its entries, so that [`TypeHints::validate`] reports
+    /// the others. The path of the root tag is empty.
+    pub fn close(&mut self, path: &str) -> Result<(), TypeHintsError> {
+        let path =
+            normalize_path(path).ok_or_else(|| TypeHintsError::InvalidPath(path.to_string()))?;
+        self.closed.insert(path);
+        Ok(())
+    }
+
+    /// Adds the hints of another tag found at the path, e.g. of an item at `Inventory[]`.
+    ///
+    /// Existing hints take precedence.
+    pub fn extend_at(&mut self, path: &str, other: &TypeHints) -> Result<(), TypeHintsError> {
+        let path =
+            normalize_path(path).ok_or_else(|| TypeHintsError::InvalidPath(path.to_string()))?;
+        let join = |v: &str| match (path.is_empty(), v.is_empty()) {
+            (true, _) => v.to_string(),
+            (false, true) => path.clone(),
+            (false, false) => format!("{}.{}", path, v),
+        };
+        for (k, v) in other.hints.iter() {
+            self.hints.entry(join(k)).or_insert_with(|| v.clone());
+        }
+        self.closed.extend(other.closed.iter().map(|v| join(v)));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    /// Adds the type suffixes to the keys of entries with hints, unless already suffixed.
+    ///
+    /// Documents in the typed format are left as they are.
+    pub fn apply(&self, json: &mut Map<String, Value>) {
+        if self.is_empty() || json.contains_key(FORMAT_KEY) {
+            return;
+        }
+        for (_, v) in json.iter_mut() {
+            if let Value::Object(v) = v {
+                self.apply_compound(v, &mut vec![]);
+            }
+        }
+    }
+
+    fn hint(&self, path: &[PathSegment]) -> Option<&Hint> {
+        self.hints.get(&path::to_string(&any_index(path)))
+    }
+
+    // The type used for suffixes
+    fn get(&self, path: &[PathSegment]) -> Option<u8> {
+        self.hint(path).map(|v| v.types[0])
+    }
+
+    /// Validates a root tag against the hints, reporting every tag of another type, number out
+    /// of range, missing required tag and unknown tag in a closed compound.
+    pub fn validate(&self, root: &TagType) -> Vec<SchemaViolation> {
+        // The names of required tags by the paths of their compounds
+        let mut required: HashMap<String, Vec<String>> = HashMap::new();
+        for (k, _) in self.hints.iter().filter(|(_, v)| v.required) {
+            let mut path = path::parse(k).unwrap_or_default();
+            if let Some(PathSegment::Name(name)) = path.pop() {
+                required
+                    .entry(path::to_string(&path))
+                    .or_default()
+                    .push(name);
+            }
+        }
+        required.values_mut().for_each(|v| v.sort());
+        let mut violations = vec![];
+        self.validate_tag(root, &mut vec![], &required, &mut violations);
+        violations
+    }
+
+    fn validate_tag(
+        &self,
+        tag: &TagType,
+        path: &mut Vec<PathSegment>,
+        required: &HashMap<String, Vec<String>>,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        let actual = u8::from(tag);
+        let hint = self.hint(path).filter(|_| !path.is_empty());
+        if let Some(hint) = hint {
+            if !hint.types.contains(&actual) {
+                violations.push(SchemaViolation {
+                    path: path::to_string(path),
+                    kind: SchemaViolationKind::MismatchedType {
+                        expected: hint.types.clone(),
+                        actual,
+                    },
+                });
+                return;
+            }
+            let below = |min: Option<f64>, v: f64| min.is_some_and(|min| v < min);
+            let above = |max: Option<f64>, v: f64| max.is_some_and(|max| v > max);
+            match number(tag) {
+                Some(value) if below(hint.min, value) || above(hint.max, value) => {
+                    violations.push(SchemaViolation {
+                        path: path::to_string(path),
+                        kind: SchemaViolationKind::OutOfRange {
+                            value,
+                            min: hint.min,
+                            max: hint.max,
+                        },
+                    });
+                }
+                _ => {}
+            }
+        }
+        match tag {
+            TagType::List(_, v) => {
+                for (i, v) in v.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    self.validate_tag(v, path, required, violations);
+                    path.pop();
+                }
+            }
+            TagType::Compound(v) => {
+                let compound_path = path::to_string(&any_index(path));
+                let is_closed = self.closed.contains(&compound_path);
+                let mut names = HashSet::with_capacity(v.len());
+                for (k, v) in v.iter() {
+                    let name = cesu8_to_string(k);
+                    path.push(PathSegment::Name(name.clone()));
+                    if is_closed && self.get(path).is_none() {
+                        violations.push(SchemaViolation {
+                            path: path::to_string(path),
+                            kind: SchemaViolationKind::UnknownTag,
+                        });
+                    } else {
+                        self.validate_tag(v, path, required, violations);
+                    }
+                    path.pop();
+                    names.insert(name);
+                }
+                for name in required.get(&compound_path).into_iter().flatten() {
+                    if !names.contains(name) {
+                        path.push(PathSegment::Name(name.clone()));
+                        violations.push(SchemaViolation {
+                            path: path::to_string(path),
+                            kind: SchemaViolationKind::MissingTag,
+                        });
+                        path.pop();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_compound(&self, json: &mut Map<String, Value>, path: &mut Vec<PathSegment>) {
+        // Rebuilt to keep the order of entries while renaming them
+        let entries = std::mem::take(json);
+        // Keys which would replace another one when suffixed are left to the resolver, which
+        // rejects the duplicate name.
+        let keys = entries.keys().cloned().collect::<HashSet<_>>();
+        for (k, mut v) in entries {
+            let (name, suffix) = split_key(&k);
+            path.push(PathSegment::Name(name.into_owned()));
+            let k = match suffix {
+                Some(_) => k,
+                None => match self
+                    .suffix(&v, path)
+                    .map(|suffix| format!("{};{}", k, suffix))
+                {
+                    Some(suffixed) if !keys.contains(&suffixed) => suffixed,
+                    _ => k,
+                },
+            };
+            self.apply_value(&mut v, path);
+            path.pop();
+            json.insert(k, v);
+        }
+    }
+
+    fn apply_value(&self, json: &mut Value, path: &mut Vec<PathSegment>) {
+        match json {
+            Value::Object(v) => self.apply_compound(v, path),
+            Value::Array(v) => {
+                path.push(PathSegment::AnyIndex);
+                v.iter_mut().for_each(|v| self.apply_value(v, path));
+                path.pop();
+            }
+            _ => {}
+        }
+    }
+
+    // The suffix of a value at the path, e.g. B for a byte or TAG_List;TAG_Float for a list
+    fn suffix(&self, json: &Value, path: &mut Vec<PathSegment>) -> Option<String> {
+        let tag_type = self.get(path);
+        let suffix = match (tag_type, json) {
+            (Some(0x01), Value::Number(_) | Value::Bool(_)) => "B",
+            (Some(0x02), Value::Number(_)) => "S",
+            (Some(0x04), Value::Number(_) | Value::String(_)) => "L",
+            (Some(0x05), Value::Number(_) | Value::String(_)) => "F",
+            (Some(0x06), Value::Number(_) | Value::String(_)) => "D",
+            (Some(0x07), Value::Array(_)) => "B",
+            (Some(0x0b), Value::Array(_)) => "I",
+            (Some(0x0c), Value::Array(_)) => "L",
+            (Some(0x09), Value::Array(v)) => return self.list_suffix(v, path),
+            // Lists with hints for their items
+            (None, Value::Array(v)) if self.has_item_hints(path) => {
+                return self.list_suffix(v, path);
+            }
+            _ => return None,
+        };
+        Some(suffix.to_string())
+    }
+
+    fn has_item_hints(&self, path: &[PathSegment]) -> bool {
+        let prefix = format!("{}[]", path::to_string(path));
+        self.hints.keys().any(|v| v.starts_with(&prefix))
+    }
+
+    fn list_suffix(&self, items: &[Value], path: &mut Vec<PathSegment>) -> Option<String> {
+        path.push(PathSegment::AnyIndex);
+        let item_type = self.get(path);
+        let suffix = match item_type {
+            // Nested lists name the item types of every level.
+            Some(0x09) | None if items.first().is_some_and(Value::is_array) => {
+                let nested = items.iter().find_map(|v| match v {
+                    Value::Array(v) if !v.is_empty() => Some(&v[..]),
+                    _ => None,
+                });
+                self.list_suffix(nested.unwrap_or(&[]), path)
+                    .map(|v| format!("TAG_List;{}", v))
+            }
+            Some(item_type) => TagType::u8_to_str(item_type).ok().map(str::to_string),
+            None => infer_item_type(items.first()).map(str::to_string),
+        };
+        path.pop();
+        suffix
+    }
+}
+
+// Only [] is meaningful in hints, as they apply to all items of lists.
+fn normalize_path(path: &str) -> Option<String> {
+    path::parse(path)
+        .filter(|v| !v.iter().any(|v| matches!(v, PathSegment::Index(_))))
+        .map(|v| path::to_string(&v))
+}
+
+fn any_index(path: &[PathSegment]) -> Vec<PathSegment> {
+    path.iter()
+        .map(|v| match v {
+            PathSegment::Index(_) => PathSegment::AnyIndex,
+            v => v.clone(),
+        })
+        .collect()
+}
+
+// The item type of a list without hint for its items, following the default inference
+fn infer_item_type(item: Option<&Value>) -> Option<&'static str> {
+    Some(match item {
+        None => "TAG_End",
+        Some(Value::Number(v)) if v.is_f64() => "TAG_Double",
+        Some(Value::Number(_)) => "TAG_Int",
+        Some(Value::String(_)) => "TAG_String",
+        Some(Value::Object(_)) => "TAG_Compound",
+        Some(_) => return None,
+    })
+}
+
+// The value of a numeric tag, for ranges
+fn number(tag: &TagType) -> Option<f64> {
+    Some(match tag {
+        TagType::Byte(v) => *v as f64,
+        TagType::Short(v) => *v as f64,
+        TagType::Int(v) => *v as f64,
+        TagType::Long(v) => i64::from(v.clone()) as f64,
+        TagType::Float(v) => *v as f64,
+        TagType::Double(v) => *v,
+        _ => return None,
+    })
+}
@@ -0,0 +1,89 @@
+//! The `LZ4Block` stream format of lz4-java, which Minecraft uses for LZ4 compressed chunks.
+
+use bytes::Buf;
+use bytes::BufMut;
+use std::io;
+use twox_hash::XxHash32;
+
+pub(crate) const MAGIC: &[u8] = b"LZ4Block";
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 4 + 4 + 4;
+const BLOCK_SIZE: usize = 1 << 16;
+const COMPRESSION_METHOD_RAW: u8 = 0x10;
+const COMPRESSION_METHOD_LZ4: u8 = 0x20;
+// log2(BLOCK_SIZE) - 10
+const COMPRESSION_LEVEL: u8 = 6;
+const COMPRESSION_LEVEL_BASE: u8 = 10;
+const CHECKSUM_SEED: u32 = 0x9747b28c;
+
+fn checksum(block: &[u8]) -> u32 {
+    XxHash32::oneshot(CHECKSUM_SEED, block) & 0x0fffffff
+}
+
+fn put_header(buf: &mut Vec<u8>, method: u8, compressed_len: usize, len: usize, check: u32) {
+    buf.put_slice(MAGIC);
+    buf.put_u8(method | COMPRESSION_LEVEL);
+    buf.put_u32_le(compressed_len as u32);
+    buf.put_u32_le(len as u32);
+    buf.put_u32_le(check);
+}
+
+pub(crate) fn compress(nbt: &[u8]) -> Vec<u8> {
+    let mut buf = vec![];
+    for block in nbt.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+        let (method, data) = if compressed.len() < block.len() {
+            (COMPRESSION_METHOD_LZ4, &compressed[..])
+        } else {
+            (COMPRESSION_METHOD_RAW, block)
+        };
+        put_header(&mut buf, method, data.len(), block.len(), checksum(block));
+        buf.put_slice(data);
+    }
+    // An empty block marks the end of the stream.
+    put_header(&mut buf, COMPRESSION_METHOD_RAW, 0, 0, 0);
+    buf
+}
+
+pub(crate) fn decompress(nbt: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid_data = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason);
+    let mut nbt = nbt;
+    let mut buf = vec![];
+    loop {
+        if nbt.len() < HEADER_LENGTH {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if !nbt.starts_with(MAGIC) {
+            return Err(invalid_data("Invalid LZ4 block magic."));
+        }
+        nbt.advance(MAGIC.len());
+        let token = nbt.get_u8();
+        let method = token & 0xf0;
+        let compressed_len = nbt.get_u32_le() as usize;
+        let len = nbt.get_u32_le() as usize;
+        let check = nbt.get_u32_le();
+        if len == 0 {
+            break;
+        }
+        // The block size told by the compression level, at most 32 MiB, bounds the allocation.
+        let max_len = 1 << (COMPRESSION_LEVEL_BASE + (token & 0x0f));
+        if max_len < len || max_len < compressed_len {
+            return Err(invalid_data("LZ4 block too large."));
+        }
+        if nbt.len() < compressed_len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (data, rest) = nbt.split_at(compressed_len);
+        let block = match method {
+            COMPRESSION_METHOD_RAW if compressed_len == len => data.to_vec(),
+            COMPRESSION_METHOD_LZ4 => lz4_flex::block::decompress(data, len)
+                .map_err(|_| invalid_data("Corrupt LZ4 block."))?,
+            _ => return Err(invalid_data("Unknown LZ4 block compression method.")),
+        };
+        if block.len() != len || checksum(&block) != check {
+            return Err(invalid_data("LZ4 block checksum mismatch."));
+        }
+        buf.extend(block);
+        nbt = rest;
+    }
+    Ok(buf)
+}
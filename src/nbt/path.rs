@@ -0,0 +1,97 @@
+//! Tag paths like `Data.Player.Pos[0]`, as printed in [`NbtDecodeError`](super::NbtDecodeError).
+//!
+//! Names are separated by `.` and list indices follow in brackets, or `[]` for any index. Names
+//! containing `.`, `[`, `]` or `"` are quoted like `"minecraft:custom.name"`, escaping `"` and `\`
+//! with a backslash.
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum PathSegment {
+    Name(String),
+    Index(usize),
+    AnyIndex,
+}
+
+/// Splits a path into its segments, or returns `None` if it is malformed.
+pub(crate) fn parse(path: &str) -> Option<Vec<PathSegment>> {
+    let mut segments = vec![];
+    let mut chars = path.chars().peekable();
+    if chars.peek().is_none() {
+        return Some(segments);
+    }
+    loop {
+        match chars.peek() {
+            Some('[') => {}
+            Some('"') => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => name.push(chars.next()?),
+                        c => name.push(c),
+                    }
+                }
+                segments.push(PathSegment::Name(name));
+            }
+            _ => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if matches!(c, '.' | '[' | ']' | '"') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                segments.push(PathSegment::Name(name));
+            }
+        }
+        while chars.peek() == Some(&'[') {
+            chars.next();
+            let mut index = String::new();
+            loop {
+                match chars.next()? {
+                    ']' => break,
+                    c => index.push(c),
+                }
+            }
+            segments.push(match index.is_empty() {
+                true => PathSegment::AnyIndex,
+                false => PathSegment::Index(index.parse().ok()?),
+            });
+        }
+        match chars.next() {
+            Some('.') => {}
+            None => return Some(segments),
+            _ => return None,
+        }
+    }
+}
+
+/// Joins segments into a path, the reverse of [`parse`].
+pub(crate) fn to_string(segments: &[PathSegment]) -> String {
+    let mut path = String::new();
+    for (i, segment) in segments.iter().enumerate() {
+        match segment {
+            PathSegment::Name(name) => {
+                if i != 0 {
+                    path.push('.');
+                }
+                if name.contains(['.', '[', ']', '"']) {
+                    path.push('"');
+                    for c in name.chars() {
+                        if matches!(c, '"' | '\\') {
+                            path.push('\\');
+                        }
+                        path.push(c);
+                    }
+                    path.push('"');
+                } else {
+                    path.push_str(name);
+                }
+            }
+            PathSegment::Index(i) => path.push_str(&format!("[{}]", i)),
+            PathSegment::AnyIndex => path.push_str("[]"),
+        }
+    }
+    path
+}
@@ -0,0 +1,440 @@
+use super::path;
+use super::path::PathSegment;
+use super::Endianness;
+use super::LongNumber;
+use super::NbtDecodeError;
+use super::NbtDecodeErrorKind;
+use super::NbtMode;
+use super::TagType;
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::io;
+use std::io::Read;
+
+/// An event yielded by [`NbtReader`].
+///
+/// `name` is the name of the tag, or `None` for list items.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NbtEvent {
+    /// The start of a compound, whose entries follow until the matching [`NbtEvent::End`].
+    CompoundStart { name: Option<String> },
+    /// The start of a list, whose `len` items follow until the matching [`NbtEvent::End`].
+    ListStart {
+        name: Option<String>,
+        item_type: u8,
+        len: usize,
+    },
+    /// A tag other than compounds and lists.
+    Value {
+        name: Option<String>,
+        value: TagType<'static>,
+    },
+    /// The end of the innermost compound or list.
+    End,
+}
+
+enum Frame {
+    Compound,
+    List {
+        item_type: u8,
+        len: usize,
+        next: usize,
+    },
+}
+
+/// A pull-based reader of uncompressed binary NBT.
+///
+/// Tags are read one at a time from the underlying reader and yielded as [`NbtEvent`]s, so only
+/// the tag being read is held in memory. Compressed NBT can be read through
+/// [`FileType::decoder`](super::FileType::decoder).
+pub struct NbtReader<R> {
+    reader: R,
+    offset: usize,
+    path: Vec<PathSegment>,
+    stack: Vec<Frame>,
+    endianness: Endianness,
+    mode: NbtMode,
+    started: bool,
+    done: bool,
+}
+
+macro_rules! get_number {
+    ($name:ident, $t:ty) => {
+        fn $name(&mut self) -> Result<$t, NbtDecodeError> {
+            let buf = self.take_array()?;
+            Ok(match self.endianness {
+                Endianness::Big => <$t>::from_be_bytes(buf),
+                Endianness::Little => <$t>::from_le_bytes(buf),
+            })
+        }
+    };
+}
+
+impl<R: Read> NbtReader<R> {
+    pub fn new(reader: R, endianness: Endianness, mode: NbtMode) -> Self {
+        Self {
+            reader,
+            offset: 0,
+            path: vec![],
+            stack: vec![],
+            endianness,
+            mode,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// The byte offset of the next tag.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The path of the innermost open compound or list, e.g. `Data.Player.Pos`.
+    pub fn path(&self) -> String {
+        // The root tag name is not a part of the path.
+        path::to_string(self.path.get(1..).unwrap_or_default())
+    }
+
+    /// Reads the next event, or returns `None` once the root tag has been read.
+    pub fn next_event(&mut self) -> Result<Option<NbtEvent>, NbtDecodeError> {
+        if self.done {
+            return Ok(None);
+        }
+        let event = self.read_event();
+        if !matches!(event, Ok(Some(_))) {
+            self.done = true;
+        }
+        event
+    }
+
+    fn read_event(&mut self) -> Result<Option<NbtEvent>, NbtDecodeError> {
+        if !self.started {
+            self.started = true;
+            let tag_type = self.get_tag_type()?;
+            if tag_type == 0x00 {
+                return Ok(None);
+            }
+            let name = self.get_root_name()?;
+            return self.get_tag(Some(name), tag_type).map(Some);
+        }
+        match self.stack.last_mut() {
+            None => Ok(None),
+            Some(Frame::Compound) => {
+                let tag_type = self.get_tag_type()?;
+                if tag_type == 0x00 {
+                    return Ok(Some(self.end()));
+                }
+                let name = self.get_string()?;
+                self.get_tag(Some(name), tag_type).map(Some)
+            }
+            Some(Frame::List {
+                item_type,
+                len,
+                next,
+            }) => {
+                if next == len {
+                    return Ok(Some(self.end()));
+                }
+                let item_type = *item_type;
+                *next += 1;
+                self.get_tag(None, item_type).map(Some)
+            }
+        }
+    }
+
+    fn end(&mut self) -> NbtEvent {
+        self.stack.pop();
+        self.path.pop();
+        NbtEvent::End
+    }
+
+    fn get_tag(&mut self, name: Option<String>, tag_type: u8) -> Result<NbtEvent, NbtDecodeError> {
+        self.path.push(match (&name, self.stack.last()) {
+            (Some(name), _) => PathSegment::Name(name.clone()),
+            (None, Some(Frame::List { next, .. })) => PathSegment::Index(next - 1),
+            (None, _) => PathSegment::Index(0),
+        });
+        let event = match tag_type {
+            0x09 => {
+                let item_type = self.get_tag_type()?;
+                let len = self.get_len()?;
+                self.stack.push(Frame::List {
+                    item_type,
+                    len,
+                    next: 0,
+                });
+                return Ok(NbtEvent::ListStart {
+                    name,
+                    item_type,
+                    len,
+                });
+            }
+            0x0a => {
+                self.stack.push(Frame::Compound);
+                return Ok(NbtEvent::CompoundStart { name });
+            }
+            _ => NbtEvent::Value {
+                name,
+                value: self.get_payload(tag_type)?,
+            },
+        };
+        self.path.pop();
+        Ok(event)
+    }
+
+    fn error_at(&self, offset: usize, kind: NbtDecodeErrorKind) -> NbtDecodeError {
+        NbtDecodeError {
+            offset,
+            path: self.path(),
+            kind,
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), NbtDecodeError> {
+        if let Err(err) = self.reader.read_exact(buf) {
+            let kind = match err.kind() {
+                io::ErrorKind::UnexpectedEof => NbtDecodeErrorKind::UnexpectedEof,
+                kind => NbtDecodeErrorKind::Io(kind),
+            };
+            return Err(self.error_at(self.offset, kind));
+        }
+        self.offset += buf.len();
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<Vec<u8>, NbtDecodeError> {
+        // Read in bounded steps so that a corrupt length does not allocate a huge buffer.
+        let mut buf = vec![];
+        while buf.len() < len {
+            let start = buf.len();
+            buf.resize(len.min(start + 0x10000), 0);
+            self.read_exact(&mut buf[start..])?;
+        }
+        Ok(buf)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], NbtDecodeError> {
+        let mut buf = [0; N];
+        self.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    // Reads the fixed-size numbers of an array at once through take
+    fn get_array<T, const N: usize>(
+        &mut self,
+        len: usize,
+        from_be_bytes: fn([u8; N]) -> T,
+        from_le_bytes: fn([u8; N]) -> T,
+    ) -> Result<Vec<T>, NbtDecodeError> {
+        let from_bytes = match self.endianness {
+            Endianness::Big => from_be_bytes,
+            Endianness::Little => from_le_bytes,
+        };
+        let buf = self.take(len.saturating_mul(N))?;
+        Ok(buf
+            .chunks_exact(N)
+            .map(|v| from_bytes(v.try_into().unwrap()))
+            .collect())
+    }
+
+    get_number!(get_u8, u8);
+    get_number!(get_i8, i8);
+    get_number!(get_u16, u16);
+    get_number!(get_i16, i16);
+    get_number!(get_f32, f32);
+    get_number!(get_f64, f64);
+
+    // Bedrock Edition network NBT
+    fn is_varint(&self) -> bool {
+        self.endianness == Endianness::Little && self.mode == NbtMode::Network
+    }
+
+    fn get_varint(&mut self, max_len: usize) -> Result<u64, NbtDecodeError> {
+        let offset = self.offset;
+        let mut value = 0;
+        for i in 0..max_len {
+            let [b] = self.take_array()?;
+            value |= ((b & 0x7f) as u64) << (7 * i);
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(self.error_at(offset, NbtDecodeErrorKind::InvalidVarInt))
+    }
+
+    fn get_i32(&mut self) -> Result<i32, NbtDecodeError> {
+        if self.is_varint() {
+            let n = self.get_varint(5)? as u32;
+            return Ok((n >> 1) as i32 ^ -((n & 1) as i32));
+        }
+        let buf = self.take_array()?;
+        Ok(match self.endianness {
+            Endianness::Big => i32::from_be_bytes(buf),
+            Endianness::Little => i32::from_le_bytes(buf),
+        })
+    }
+
+    fn get_i64(&mut self) -> Result<i64, NbtDecodeError> {
+        if self.is_varint() {
+            let n = self.get_varint(10)?;
+            return Ok((n >> 1) as i64 ^ -((n & 1) as i64));
+        }
+        let buf = self.take_array()?;
+        Ok(match self.endianness {
+            Endianness::Big => i64::from_be_bytes(buf),
+            Endianness::Little => i64::from_le_bytes(buf),
+        })
+    }
+
+    /// Reads a tag id and rejects ids that do not name a known tag type.
+    fn get_tag_type(&mut self) -> Result<u8, NbtDecodeError> {
+        let offset = self.offset;
+        let tag_type = self.get_u8()?;
+        if TagType::u8_to_str(tag_type).is_err() {
+            return Err(self.error_at(offset, NbtDecodeErrorKind::UnknownTagId(tag_type)));
+        }
+        Ok(tag_type)
+    }
+
+    /// Reads the i32 length prefix of arrays and lists.
+    fn get_len(&mut self) -> Result<usize, NbtDecodeError> {
+        let offset = self.offset;
+        let len = self.get_i32()?;
+        if len < 0 {
+            return Err(self.error_at(offset, NbtDecodeErrorKind::NegativeArrayLength(len)));
+        }
+        Ok(len as usize)
+    }
+
+    /// Reads a length-prefixed CESU-8 string, as used by tag names and TAG_String.
+    ///
+    /// The length is a u16, or an unsigned varint in Bedrock Edition network NBT.
+    fn get_string(&mut self) -> Result<String, NbtDecodeError> {
+        let offset = self.offset;
+        let len = if self.is_varint() {
+            self.get_varint(5)? as usize
+        } else {
+            self.get_u16()? as usize
+        };
+        let buf = self.take(len)?;
+        match cesu8::from_java_cesu8(&buf) {
+            Ok(string) => Ok(string.into_owned()),
+            Err(_) => Err(self.error_at(offset, NbtDecodeErrorKind::InvalidCesu8)),
+        }
+    }
+
+    /// Reads the name of the root tag, which is omitted in Java Edition network NBT.
+    fn get_root_name(&mut self) -> Result<String, NbtDecodeError> {
+        if self.endianness == Endianness::Big && self.mode == NbtMode::Network {
+            return Ok("".to_string());
+        }
+        self.get_string()
+    }
+
+    // The payload of tags other than compounds and lists
+    fn get_payload(&mut self, tag_type: u8) -> Result<TagType<'static>, NbtDecodeError> {
+        let value = match tag_type {
+            0x00 => TagType::End,
+            0x01 => TagType::Byte(self.get_i8()?),
+            0x02 => TagType::Short(self.get_i16()?),
+            0x03 => TagType::Int(self.get_i32()?),
+            0x04 => TagType::Long(LongNumber::from(self.get_i64()?)),
+            0x05 => TagType::Float(self.get_f32()?),
+            0x06 => TagType::Double(self.get_f64()?),
+            0x07 => {
+                let len = self.get_len()?;
+                TagType::ByteArray(self.take(len)?.into_iter().map(|v| v as i8).collect())
+            }
+            0x08 => {
+                let string = self.get_string()?;
+                TagType::String(Cow::Owned(cesu8::to_java_cesu8(&string).into_owned()))
+            }
+            0x0b => {
+                let len = self.get_len()?;
+                TagType::IntArray(match self.is_varint() {
+                    true => (0..len).map(|_| self.get_i32()).collect::<Result<_, _>>()?,
+                    false => self.get_array(len, i32::from_be_bytes, i32::from_le_bytes)?,
+                })
+            }
+            0x0c => {
+                let len = self.get_len()?;
+                let buf = match self.is_varint() {
+                    true => (0..len).map(|_| self.get_i64()).collect::<Result<_, _>>()?,
+                    false => self.get_array(len, i64::from_be_bytes, i64::from_le_bytes)?,
+                };
+                TagType::LongArray(buf.into_iter().map(LongNumber::from).collect())
+            }
+            _ => {
+                return Err(self.error_at(self.offset, NbtDecodeErrorKind::UnknownTagId(tag_type)));
+            }
+        };
+        Ok(value)
+    }
+
+    /// Reads all the remaining events and builds the root tag from them, keyed by the root name.
+    pub(crate) fn read_root(
+        &mut self,
+    ) -> Result<IndexMap<Cow<'static, [u8]>, TagType<'static>>, NbtDecodeError> {
+        let mut root = IndexMap::new();
+        let mut builder = TagBuilder::default();
+        while let Some(event) = self.next_event()? {
+            if let Some((name, value)) = builder.push(event) {
+                root.insert(to_cesu8(name), value);
+            }
+        }
+        Ok(root)
+    }
+}
+
+/// Builds tags from [`NbtEvent`]s.
+#[derive(Default)]
+pub(crate) struct TagBuilder {
+    // The open compounds and lists with their names
+    stack: Vec<(Option<String>, TagType<'static>)>,
+}
+
+impl TagBuilder {
+    /// Whether a compound or a list is being built.
+    pub(crate) fn is_building(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    /// Adds an event to the tag being built, returning the tag with its name once it is complete.
+    pub(crate) fn push(&mut self, event: NbtEvent) -> Option<(Option<String>, TagType<'static>)> {
+        let (name, value) = match event {
+            NbtEvent::CompoundStart { name } => {
+                self.stack.push((name, TagType::Compound(IndexMap::new())));
+                return None;
+            }
+            NbtEvent::ListStart {
+                name, item_type, ..
+            } => {
+                self.stack.push((name, TagType::List(item_type, vec![])));
+                return None;
+            }
+            NbtEvent::Value { name, value } => (name, value),
+            // Every End has a matching start.
+            NbtEvent::End => self.stack.pop().unwrap(),
+        };
+        match self.stack.last_mut() {
+            Some((_, TagType::Compound(v))) => {
+                v.insert(to_cesu8(name), value);
+            }
+            Some((_, TagType::List(_, v))) => v.push(value),
+            _ => return Some((name, value)),
+        }
+        None
+    }
+}
+
+fn to_cesu8(name: Option<String>) -> Cow<'static, [u8]> {
+    Cow::Owned(cesu8::to_java_cesu8(&name.unwrap_or_default()).into_owned())
+}
+
+impl<R: Read> Iterator for NbtReader<R> {
+    type Item = Result<NbtEvent, NbtDecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
@@ -1,14 +1,21 @@
+use super::f32_from_non_finite_str;
+use super::f64_from_non_finite_str;
+use super::path;
+use super::path::PathSegment;
+use super::split_key;
+use super::LongNumber;
 use super::TagResolvingError;
+use super::TagType;
 use super::ANONYMOUS_KEY;
-use super::{LongNumber, TagType};
+use indexmap::IndexMap;
 use serde_json::Map;
 use serde_json::Number;
 use serde_json::Value;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::str::FromStr;
 
 fn resolve_byte<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &Number,
 ) -> Result<(), TagResolvingError> {
@@ -26,7 +33,7 @@ fn resolve_byte<'a>(
 }
 
 fn resolve_short<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &Number,
 ) -> Result<(), TagResolvingError> {
@@ -44,7 +51,7 @@ fn resolve_short<'a>(
 }
 
 fn resolve_int<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &Number,
 ) -> Result<(), TagResolvingError> {
@@ -62,11 +69,16 @@ fn resolve_int<'a>(
 }
 
 fn resolve_long<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
-    v: &Number,
+    v: &Value,
 ) -> Result<(), TagResolvingError> {
-    let v = v.as_i64();
+    let v = match v {
+        Value::Number(v) => v.as_i64(),
+        // Longs out of the safe integer range of JavaScript are written as strings.
+        Value::String(v) => i64::from_str(v).ok(),
+        _ => None,
+    };
     match v {
         Some(v) => {
             nbt.insert(name, TagType::Long(LongNumber::from(v)));
@@ -77,14 +89,19 @@ fn resolve_long<'a>(
 }
 
 fn resolve_float<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
-    v: &Number,
+    v: &Value,
 ) -> Result<(), TagResolvingError> {
-    let v = v.as_f64();
+    let v = match v {
+        // Parsed from the decimal representation, as rounding the f64 may give a different value.
+        Value::Number(v) => f32::from_str(&v.to_string()).ok(),
+        Value::String(v) => f32_from_non_finite_str(v),
+        _ => None,
+    };
     match v {
         Some(v) => {
-            nbt.insert(name, TagType::Float(v as f32));
+            nbt.insert(name, TagType::Float(v));
         }
         None => return Err(TagResolvingError::UnsupportedValue),
     }
@@ -92,11 +109,15 @@ fn resolve_float<'a>(
 }
 
 fn resolve_double<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
-    v: &Number,
+    v: &Value,
 ) -> Result<(), TagResolvingError> {
-    let v = v.as_f64();
+    let v = match v {
+        Value::Number(v) => v.as_f64(),
+        Value::String(v) => f64_from_non_finite_str(v),
+        _ => None,
+    };
     match v {
         Some(v) => {
             nbt.insert(name, TagType::Double(v));
@@ -107,13 +128,13 @@ fn resolve_double<'a>(
 }
 
 fn resolve_byte_array<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &[Value],
 ) -> Result<(), TagResolvingError> {
     let mut buf = vec![];
     for v in v.iter() {
-        let mut buf1 = HashMap::new();
+        let mut buf1 = IndexMap::new();
         match v {
             Value::Number(v) => resolve_byte(&mut buf1, cesu8::to_java_cesu8(""), v)?,
             _ => return Err(TagResolvingError::UnsupportedValue),
@@ -128,7 +149,7 @@ fn resolve_byte_array<'a>(
 }
 
 fn resolve_string<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &'a str,
 ) -> Result<(), TagResolvingError> {
@@ -137,151 +158,167 @@ fn resolve_string<'a>(
 }
 
 fn resolve_list<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &'a [Value],
     v_type: String,
 ) -> Result<(), TagResolvingError> {
+    // v_type is the item type, followed by the item type of nested lists, e.g. TAG_List;TAG_Int
+    let (item_type, nested_type) = match v_type.split_once(";") {
+        Some((item_type, nested_type)) => (item_type, Some(nested_type)),
+        None => (v_type.as_str(), None),
+    };
+    let item_type_id =
+        TagType::str_to_u8(item_type).map_err(|_| TagResolvingError::UnsupportedValue)?;
     let mut buf = vec![];
-    for v in v.iter() {
-        let mut buf1 = HashMap::new();
-        match v_type {
-            ref v_type if v_type.starts_with("TAG_Byte") => match v {
-                Value::Number(v) => resolve_byte(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Short") => match v {
-                Value::Number(v) => resolve_short(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Int") => match v {
-                Value::Number(v) => resolve_int(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Long") => match v {
-                Value::Number(v) => resolve_long(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Float") => match v {
-                Value::Number(v) => resolve_float(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Double") => match v {
-                Value::Number(v) => resolve_double(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Byte_Array") => match v {
-                Value::Array(v) => resolve_byte_array(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_String") => match v {
-                Value::String(v) => resolve_string(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_List") => match v {
-                Value::Array(v) => {
-                    let v_type = v_type.split(";").collect::<Vec<_>>();
-                    if v_type.len() == 1 {
-                        return Err(TagResolvingError::UnsupportedValue);
-                    } else {
-                        let v_type = v_type[1].to_string();
-                        resolve_list(&mut buf1, ANONYMOUS_KEY.clone(), v, v_type)?;
+    for (i, v) in v.iter().enumerate() {
+        let mut buf1 = IndexMap::new();
+        resolve_item(&mut buf1, item_type, nested_type, v)
+            .map_err(|err| err.within(PathSegment::Index(i)))?;
+        buf.append(&mut buf1.into_values().collect());
+    }
+    nbt.insert(name, TagType::List(item_type_id, buf));
+    Ok(())
+}
+
+// Resolves a list item of the item type into nbt, keyed by the anonymous name
+fn resolve_item<'a>(
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
+    item_type: &str,
+    nested_type: Option<&str>,
+    v: &'a Value,
+) -> Result<(), TagResolvingError> {
+    match item_type {
+        "TAG_Byte" => match v {
+            Value::Number(v) => resolve_byte(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        "TAG_Short" => match v {
+            Value::Number(v) => resolve_short(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        "TAG_Int" => match v {
+            Value::Number(v) => resolve_int(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        "TAG_Long" => resolve_long(nbt, ANONYMOUS_KEY.clone(), v)?,
+        "TAG_Float" => resolve_float(nbt, ANONYMOUS_KEY.clone(), v)?,
+        "TAG_Double" => resolve_double(nbt, ANONYMOUS_KEY.clone(), v)?,
+        "TAG_Byte_Array" => match v {
+            Value::Array(v) => resolve_byte_array(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        "TAG_String" => match v {
+            Value::String(v) => resolve_string(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        "TAG_List" => match (v, nested_type) {
+            // Each item names its own type like a compound entry without name, e.g. {";TAG_Int": []}
+            (Value::Object(v), Some("*")) => {
+                let mut item = IndexMap::new();
+                for (k, v) in v.iter() {
+                    resolve_entry(&mut item, k, v)?;
+                }
+                match item.pop() {
+                    Some((name, v @ TagType::List(..))) if name.is_empty() && item.is_empty() => {
+                        nbt.insert(name, v);
                     }
+                    _ => return Err(TagResolvingError::UnsupportedValue),
                 }
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Compound") => match v {
-                Value::Object(v) => resolve_compound(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Int_Array") => match v {
-                Value::Array(v) => resolve_int_array(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
-            ref v_type if v_type.starts_with("TAG_Long_Array") => match v {
-                Value::Array(v) => resolve_long_array(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-                _ => return Err(TagResolvingError::UnsupportedValue),
-            },
+            }
+            (Value::Array(v), Some(nested_type)) if nested_type != "*" => {
+                resolve_list(nbt, ANONYMOUS_KEY.clone(), v, nested_type.to_string())?
+            }
             _ => return Err(TagResolvingError::UnsupportedValue),
-        }
-        buf.append(&mut buf1.into_values().collect());
+        },
+        "TAG_Compound" => match v {
+            Value::Object(v) => resolve_compound(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        "TAG_Int_Array" => match v {
+            Value::Array(v) => resolve_int_array(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        "TAG_Long_Array" => match v {
+            Value::Array(v) => resolve_long_array(nbt, ANONYMOUS_KEY.clone(), v)?,
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        _ => return Err(TagResolvingError::UnsupportedValue),
     }
-    nbt.insert(name, TagType::List(buf));
     Ok(())
 }
 
 pub fn resolve_compound<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &'a Map<String, Value>,
 ) -> Result<(), TagResolvingError> {
-    let mut buf = HashMap::new();
+    let mut buf = IndexMap::new();
     for (k, v) in v.iter() {
-        match v {
-            Value::Null => return Err(TagResolvingError::UnsupportedValue),
-            Value::Bool(v) => {
-                let name = cesu8::to_java_cesu8(k);
-                resolve_byte(&mut buf, name, &Number::from(if *v { 1 } else { 0 }))?;
-            }
-            Value::Number(v) => {
-                if !k.contains(";") {
-                    let name = cesu8::to_java_cesu8(k);
-                    if v.is_f64() {
-                        resolve_double(&mut buf, name, v)?;
-                    } else {
-                        resolve_int(&mut buf, name, v)?;
-                    }
-                } else {
-                    let name = cesu8::to_java_cesu8(&k[..k.len() - 2]);
-                    match k {
-                        k if k.ends_with(";B") => resolve_byte(&mut buf, name, v)?,
-                        k if k.ends_with(";S") => resolve_short(&mut buf, name, v)?,
-                        k if k.ends_with(";L") => resolve_long(&mut buf, name, v)?,
-                        k if k.ends_with(";F") => resolve_float(&mut buf, name, v)?,
-                        _ => return Err(TagResolvingError::UnsupportedValue),
-                    }
-                }
-            }
-            Value::String(v) => {
-                let name = cesu8::to_java_cesu8(k);
-                resolve_string(&mut buf, name, v)?;
-            }
-            Value::Array(v) => {
-                let name = k.split(";").collect::<Vec<_>>();
-                if name.len() == 1 {
-                    return Err(TagResolvingError::UnsupportedValue);
-                } else {
-                    let v_type = name[1..].join(";");
-                    let name = cesu8::to_java_cesu8(name[0]);
-                    match v_type {
-                        v_type if v_type.starts_with("B") => resolve_byte_array(&mut buf, name, v)?,
-                        v_type if v_type.starts_with("I") => resolve_int_array(&mut buf, name, v)?,
-                        v_type if v_type.starts_with("L") => resolve_long_array(&mut buf, name, v)?,
-                        v_type if v_type.starts_with("TAG_") => {
-                            resolve_list(&mut buf, name, v, v_type)?
-                        }
-                        _ => return Err(TagResolvingError::UnsupportedValue),
-                    }
-                }
-            }
-            Value::Object(v) => {
-                let name = cesu8::to_java_cesu8(k);
-                resolve_compound(&mut buf, name, v)?;
-            }
+        // Keys like a;B and a;S, or a and a;;, are the same name.
+        let name = split_key(k).0;
+        if buf.contains_key(&cesu8_name(name.clone())) {
+            return Err(TagResolvingError::DuplicateName(path::to_string(&[
+                PathSegment::Name(name.into_owned()),
+            ])));
         }
+        resolve_entry(&mut buf, k, v)
+            .map_err(|err| err.within(PathSegment::Name(name.into_owned())))?;
     }
     nbt.insert(name, TagType::Compound(buf));
     Ok(())
 }
 
+fn cesu8_name(name: Cow<str>) -> Cow<[u8]> {
+    match name {
+        Cow::Borrowed(name) => cesu8::to_java_cesu8(name),
+        Cow::Owned(name) => Cow::Owned(cesu8::to_java_cesu8(&name).into_owned()),
+    }
+}
+
+/// Resolves a compound entry, whose key is the tag name followed by the type suffix if any.
+///
+/// See [`split_key`] for names containing `;`.
+pub fn resolve_entry<'a>(
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
+    k: &'a str,
+    v: &'a Value,
+) -> Result<(), TagResolvingError> {
+    let (name, suffix) = split_key(k);
+    let name = cesu8_name(name);
+    match (v, suffix) {
+        (Value::Bool(v), None | Some("B")) => {
+            resolve_byte(nbt, name, &Number::from(if *v { 1 } else { 0 }))?;
+        }
+        (Value::Number(n), None) if n.is_f64() => resolve_double(nbt, name, v)?,
+        (Value::Number(n), None) => resolve_int(nbt, name, n)?,
+        (Value::Number(n), Some("B")) => resolve_byte(nbt, name, n)?,
+        (Value::Number(n), Some("S")) => resolve_short(nbt, name, n)?,
+        // Longs out of the safe integer range and non-finite floating point values are strings.
+        (Value::Number(_) | Value::String(_), Some("L")) => resolve_long(nbt, name, v)?,
+        (Value::Number(_) | Value::String(_), Some("F")) => resolve_float(nbt, name, v)?,
+        (Value::Number(_) | Value::String(_), Some("D")) => resolve_double(nbt, name, v)?,
+        (Value::String(v), None) => resolve_string(nbt, name, v)?,
+        (Value::Array(v), Some("B")) => resolve_byte_array(nbt, name, v)?,
+        (Value::Array(v), Some("I")) => resolve_int_array(nbt, name, v)?,
+        (Value::Array(v), Some("L")) => resolve_long_array(nbt, name, v)?,
+        (Value::Array(v), Some(v_type)) if v_type.starts_with("TAG_") => {
+            resolve_list(nbt, name, v, v_type.to_string())?
+        }
+        (Value::Object(v), None) => resolve_compound(nbt, name, v)?,
+        _ => return Err(TagResolvingError::UnsupportedValue),
+    }
+    Ok(())
+}
+
 fn resolve_int_array<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &'a [Value],
 ) -> Result<(), TagResolvingError> {
     let mut buf = vec![];
     for v in v.iter() {
-        let mut buf1 = HashMap::new();
+        let mut buf1 = IndexMap::new();
         match v {
             Value::Number(v) => resolve_int(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
             _ => return Err(TagResolvingError::UnsupportedValue),
@@ -298,17 +335,14 @@ fn resolve_int_array<'a>(
 }
 
 fn resolve_long_array<'a>(
-    nbt: &mut HashMap<Cow<'a, [u8]>, TagType<'a>>,
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
     name: Cow<'a, [u8]>,
     v: &'a [Value],
 ) -> Result<(), TagResolvingError> {
     let mut buf = vec![];
     for v in v.iter() {
-        let mut buf1 = HashMap::new();
-        match v {
-            Value::Number(v) => resolve_long(&mut buf1, ANONYMOUS_KEY.clone(), v)?,
-            _ => return Err(TagResolvingError::UnsupportedValue),
-        }
+        let mut buf1 = IndexMap::new();
+        resolve_long(&mut buf1, ANONYMOUS_KEY.clone(), v)?;
         for v in buf1.values() {
             match v {
                 TagType::Long(v) => buf.push(v.clone()),
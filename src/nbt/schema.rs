@@ -0,0 +1,88 @@
+//! Schemas of well-known files of Java Edition, bundled as type hints.
+
+use super::hints::HintSource;
+use super::TypeHints;
+use super::TypeHintsError;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lower")]
+pub enum Schema {
+    /// `level.dat`
+    Level,
+    /// Player data, e.g. `playerdata/<UUID>.dat`
+    Player,
+    /// Structure templates (`.nbt`)
+    Structure,
+    /// Item stacks
+    Item,
+    /// Entities
+    Entity,
+}
+
+#[derive(Deserialize)]
+struct SchemaFile {
+    #[serde(default)]
+    closed: Vec<String>,
+    // Schemas of tags found at paths, e.g. of items in lists
+    #[serde(default)]
+    include: HashMap<String, Schema>,
+    types: HashMap<String, HintSource>,
+}
+
+impl Schema {
+    fn source(self) -> &'static str {
+        match self {
+            Schema::Level => include_str!("schemas/level.toml"),
+            Schema::Player => include_str!("schemas/player.toml"),
+            Schema::Structure => include_str!("schemas/structure.toml"),
+            Schema::Item => include_str!("schemas/item.toml"),
+            Schema::Entity => include_str!("schemas/entity.toml"),
+        }
+    }
+
+    fn load(self) -> Result<TypeHints, TypeHintsError> {
+        let file: SchemaFile = toml::from_str(self.source())
+            .map_err(|err| TypeHintsError::InvalidFile(err.to_string()))?;
+        let mut hints = TypeHints::from_map(file.types)?;
+        for path in file.closed.iter() {
+            hints.close(path)?;
+        }
+        for (path, schema) in file.include {
+            hints.extend_at(&path, &schema.load()?)?;
+        }
+        Ok(hints)
+    }
+
+    /// The type hints of the schema.
+    pub fn hints(self) -> TypeHints {
+        self.load().expect("bundled schemas are valid")
+    }
+
+    /// Guesses the schema of a file by its name: `.nbt` files are structure templates, while
+    /// others are picked by their stem whatever the extension, `level` (e.g. `level.dat` and
+    /// `level.json`) and player data named by UUID.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let stem = name.split('.').next()?;
+        if path.extension().is_some_and(|v| v == "nbt") {
+            Some(Schema::Structure)
+        } else if stem == "level" {
+            Some(Schema::Level)
+        } else if is_uuid(stem) {
+            Some(Schema::Player)
+        } else {
+            None
+        }
+    }
+}
+
+// The hyphenated form, e.g. 069a79f4-44e9-4726-a5be-fca90e38aaf5
+fn is_uuid(v: &str) -> bool {
+    let groups = v.split('-').map(str::len).collect::<Vec<_>>();
+    groups == [8, 4, 4, 4, 12] && v.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+}
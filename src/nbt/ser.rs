@@ -0,0 +1,563 @@
+use super::array::BYTE_ARRAY_NAME;
+use super::array::INT_ARRAY_NAME;
+use super::array::LONG_ARRAY_NAME;
+use super::Endianness;
+use super::LongNumber;
+use super::NbtMode;
+use super::NbtSerdeError;
+use super::NbtWriter;
+use super::TagType;
+use indexmap::IndexMap;
+use serde::ser;
+use serde::ser::Impossible;
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// Serializes a value into uncompressed big-endian NBT with an unnamed root tag.
+///
+/// Structs and maps become compounds and sequences become lists. Use [`ByteArray`](super::ByteArray),
+/// [`IntArray`](super::IntArray) and [`LongArray`](super::LongArray) for the array tags.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, NbtSerdeError> {
+    let tag = to_tag(value)?;
+    let mut nbt = NbtWriter::new(vec![], Endianness::Big, NbtMode::File);
+    nbt.write_tag("", &tag)?;
+    Ok(nbt.finish()?)
+}
+
+/// Serializes a value into a tag, see [`to_vec`].
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> Result<TagType<'static>, NbtSerdeError> {
+    match value.serialize(TagSerializer)? {
+        // None
+        TagType::End => Err(ser::Error::custom("A value is required.")),
+        tag => Ok(tag),
+    }
+}
+
+fn cesu8(v: &str) -> Cow<'static, [u8]> {
+    Cow::Owned(cesu8::to_java_cesu8(v).into_owned())
+}
+
+// Builds a list, checking that the items are of the same type
+fn to_list(items: Vec<TagType<'static>>) -> Result<TagType<'static>, NbtSerdeError> {
+    let item_type = items.first().map_or(0x00, u8::from);
+    if item_type == 0x00 && !items.is_empty() {
+        return Err(ser::Error::custom("List items cannot be None."));
+    }
+    if items.iter().any(|v| u8::from(v) != item_type) {
+        return Err(ser::Error::custom("List items must have the same type."));
+    }
+    Ok(TagType::List(item_type, items))
+}
+
+// The payload of a list serialized in a newtype struct of an array wrapper
+fn to_array(name: &str, tag: TagType<'static>) -> Result<TagType<'static>, NbtSerdeError> {
+    let TagType::List(_, items) = tag else {
+        return Err(ser::Error::custom("An array requires a sequence."));
+    };
+    let invalid = || ser::Error::custom("Invalid array item.");
+    Ok(match name {
+        BYTE_ARRAY_NAME => TagType::ByteArray(
+            items
+                .into_iter()
+                .map(|v| match v {
+                    TagType::Byte(v) => Ok(v),
+                    _ => Err(invalid()),
+                })
+                .collect::<Result<_, NbtSerdeError>>()?,
+        ),
+        INT_ARRAY_NAME => TagType::IntArray(
+            items
+                .into_iter()
+                .map(|v| match v {
+                    TagType::Int(v) => Ok(v),
+                    _ => Err(invalid()),
+                })
+                .collect::<Result<_, NbtSerdeError>>()?,
+        ),
+        _ => TagType::LongArray(
+            items
+                .into_iter()
+                .map(|v| match v {
+                    TagType::Long(v) => Ok(v),
+                    _ => Err(invalid()),
+                })
+                .collect::<Result<_, NbtSerdeError>>()?,
+        ),
+    })
+}
+
+struct TagSerializer;
+
+impl ser::Serializer for TagSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = CompoundSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Byte(v as i8))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Byte(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Short(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Long(LongNumber::from(v)))
+    }
+
+    // Unsigned integers keep their bits in the signed tag of the same size.
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Byte(v as i8))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Short(v as i16))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Int(v as i32))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Long(LongNumber::from(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Float(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::String(cesu8(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::ByteArray(v.iter().map(|v| *v as i8).collect()))
+    }
+
+    // TAG_End marks values to be omitted from compounds.
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::End)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(TagType::Compound(IndexMap::new()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let tag = value.serialize(self)?;
+        match name {
+            BYTE_ARRAY_NAME | INT_ARRAY_NAME | LONG_ARRAY_NAME => to_array(name, tag),
+            _ => Ok(tag),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let tag = value.serialize(self)?;
+        Ok(TagType::Compound(IndexMap::from([(cesu8(variant), tag)])))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SeqSerializer {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(CompoundSerializer {
+            variant: None,
+            key: None,
+            entries: IndexMap::new(),
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(CompoundSerializer {
+            variant: Some(variant),
+            key: None,
+            entries: IndexMap::new(),
+        })
+    }
+}
+
+// Wraps the value of an enum variant into a compound keyed by the variant name
+fn wrap_variant(variant: Option<&str>, tag: TagType<'static>) -> TagType<'static> {
+    match variant {
+        Some(variant) => TagType::Compound(IndexMap::from([(cesu8(variant), tag)])),
+        None => tag,
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    items: Vec<TagType<'static>>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtSerdeError> {
+        self.items.push(value.serialize(TagSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<TagType<'static>, NbtSerdeError> {
+        Ok(wrap_variant(self.variant, to_list(self.items)?))
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+struct CompoundSerializer {
+    variant: Option<&'static str>,
+    key: Option<Cow<'static, [u8]>>,
+    entries: IndexMap<Cow<'static, [u8]>, TagType<'static>>,
+}
+
+impl CompoundSerializer {
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: Cow<'static, [u8]>,
+        value: &T,
+    ) -> Result<(), NbtSerdeError> {
+        match value.serialize(TagSerializer)? {
+            // None is omitted.
+            TagType::End => {}
+            tag => {
+                self.entries.insert(key, tag);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<TagType<'static>, NbtSerdeError> {
+        Ok(wrap_variant(self.variant, TagType::Compound(self.entries)))
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        match self.key.take() {
+            Some(key) => self.insert(key, value),
+            None => Err(ser::Error::custom("A map value without key.")),
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(cesu8(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for CompoundSerializer {
+    type Ok = TagType<'static>;
+    type Error = NbtSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(cesu8(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+// Compound keys are strings, or anything written as one.
+struct KeySerializer;
+
+macro_rules! serialize_key_display {
+    ($name:ident, $t:ty) => {
+        fn $name(self, v: $t) -> Result<Self::Ok, Self::Error> {
+            Ok(cesu8(&v.to_string()))
+        }
+    };
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Cow<'static, [u8]>;
+    type Error = NbtSerdeError;
+    type SerializeSeq = Impossible<Self::Ok, Self::Error>;
+    type SerializeTuple = Impossible<Self::Ok, Self::Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Self::Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Self::Error>;
+    type SerializeMap = Impossible<Self::Ok, Self::Error>;
+    type SerializeStruct = Impossible<Self::Ok, Self::Error>;
+    type SerializeStructVariant = Impossible<Self::Ok, Self::Error>;
+
+    serialize_key_display!(serialize_bool, bool);
+    serialize_key_display!(serialize_i8, i8);
+    serialize_key_display!(serialize_i16, i16);
+    serialize_key_display!(serialize_i32, i32);
+    serialize_key_display!(serialize_i64, i64);
+    serialize_key_display!(serialize_u8, u8);
+    serialize_key_display!(serialize_u16, u16);
+    serialize_key_display!(serialize_u32, u32);
+    serialize_key_display!(serialize_u64, u64);
+    serialize_key_display!(serialize_f32, f32);
+    serialize_key_display!(serialize_f64, f64);
+    serialize_key_display!(serialize_char, char);
+    serialize_key_display!(serialize_str, &str);
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(cesu8(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(key_must_be_a_string())
+    }
+}
+
+fn key_must_be_a_string() -> NbtSerdeError {
+    ser::Error::custom("A compound key must be a string.")
+}
@@ -0,0 +1,193 @@
+//! The typed-object JSON encoding, where every tag is an object naming its type, e.g.
+//! `{"type": "byte", "value": 1}`, and lists name the type of their items in `elementType`.
+
+use super::f32_from_non_finite_str;
+use super::f32_to_json;
+use super::f64_from_non_finite_str;
+use super::f64_to_json;
+use super::long_to_json;
+use super::LongNumber;
+use super::TagResolvingError;
+use super::TagType;
+use indexmap::IndexMap;
+use serde_json::Map;
+use serde_json::Number;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+const TYPE_KEY: &str = "type";
+const ELEMENT_TYPE_KEY: &str = "elementType";
+const VALUE_KEY: &str = "value";
+
+fn type_name(tag_type: u8) -> &'static str {
+    match tag_type {
+        0x01 => "byte",
+        0x02 => "short",
+        0x03 => "int",
+        0x04 => "long",
+        0x05 => "float",
+        0x06 => "double",
+        0x07 => "byteArray",
+        0x08 => "string",
+        0x09 => "list",
+        0x0a => "compound",
+        0x0b => "intArray",
+        0x0c => "longArray",
+        _ => "end",
+    }
+}
+
+pub(crate) fn type_id(name: &str) -> Option<u8> {
+    (0x00..=0x0c).find(|v| type_name(*v) == name)
+}
+
+fn numbers<T: Copy + Into<i64>>(v: &[T]) -> Value {
+    Value::Array(
+        v.iter()
+            .map(|v| Value::Number(Number::from((*v).into())))
+            .collect(),
+    )
+}
+
+/// Converts a tag into its typed object.
+pub(crate) fn to_json(tag: &TagType) -> Value {
+    let tag_type = u8::from(tag);
+    let mut json = Map::new();
+    json.insert(TYPE_KEY.to_string(), type_name(tag_type).into());
+    let value = match tag {
+        TagType::End => Value::Null,
+        TagType::Byte(v) => Value::Number(Number::from(*v)),
+        TagType::Short(v) => Value::Number(Number::from(*v)),
+        TagType::Int(v) => Value::Number(Number::from(*v)),
+        TagType::Long(v) => long_to_json(v),
+        TagType::Float(v) => f32_to_json(*v),
+        TagType::Double(v) => f64_to_json(*v),
+        TagType::ByteArray(v) => numbers(v),
+        TagType::String(v) => Value::String(super::cesu8_to_string(v)),
+        TagType::List(item_type, v) => {
+            json.insert(ELEMENT_TYPE_KEY.to_string(), type_name(*item_type).into());
+            Value::Array(v.iter().map(to_json).collect())
+        }
+        TagType::Compound(v) => Value::Object(
+            v.iter()
+                .map(|(k, v)| (super::cesu8_to_string(k), to_json(v)))
+                .collect(),
+        ),
+        TagType::IntArray(v) => numbers(v),
+        TagType::LongArray(v) => Value::Array(v.iter().map(long_to_json).collect()),
+    };
+    json.insert(VALUE_KEY.to_string(), value);
+    Value::Object(json)
+}
+
+fn resolve_integer<T: TryFrom<i64>>(v: &Value) -> Result<T, TagResolvingError> {
+    v.as_i64()
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or(TagResolvingError::UnsupportedValue)
+}
+
+fn resolve_long(v: &Value) -> Result<LongNumber, TagResolvingError> {
+    let v = match v {
+        Value::Number(v) => v.as_i64(),
+        Value::String(v) => i64::from_str(v).ok(),
+        _ => None,
+    };
+    v.map(LongNumber::from)
+        .ok_or(TagResolvingError::UnsupportedValue)
+}
+
+fn resolve_array<T>(
+    v: &Value,
+    resolve: fn(&Value) -> Result<T, TagResolvingError>,
+) -> Result<Vec<T>, TagResolvingError> {
+    match v {
+        Value::Array(v) => v.iter().map(resolve).collect(),
+        _ => Err(TagResolvingError::UnsupportedValue),
+    }
+}
+
+/// Resolves a typed object into its tag.
+pub(crate) fn resolve(json: &Value) -> Result<TagType<'_>, TagResolvingError> {
+    let Value::Object(json) = json else {
+        return Err(TagResolvingError::UnsupportedValue);
+    };
+    let tag_type = json
+        .get(TYPE_KEY)
+        .and_then(Value::as_str)
+        .and_then(type_id)
+        .ok_or(TagResolvingError::UnsupportedValue)?;
+    let v = json
+        .get(VALUE_KEY)
+        .ok_or(TagResolvingError::UnsupportedValue)?;
+    Ok(match tag_type {
+        0x01 => TagType::Byte(resolve_integer(v)?),
+        0x02 => TagType::Short(resolve_integer(v)?),
+        0x03 => TagType::Int(resolve_integer(v)?),
+        0x04 => TagType::Long(resolve_long(v)?),
+        0x05 => TagType::Float(
+            match v {
+                // Parsed from the decimal representation like in the compact format
+                Value::Number(v) => f32::from_str(&v.to_string()).ok(),
+                Value::String(v) => f32_from_non_finite_str(v),
+                _ => None,
+            }
+            .ok_or(TagResolvingError::UnsupportedValue)?,
+        ),
+        0x06 => TagType::Double(
+            match v {
+                Value::Number(v) => v.as_f64(),
+                Value::String(v) => f64_from_non_finite_str(v),
+                _ => None,
+            }
+            .ok_or(TagResolvingError::UnsupportedValue)?,
+        ),
+        0x07 => TagType::ByteArray(resolve_array(v, resolve_integer)?),
+        0x08 => match v {
+            Value::String(v) => TagType::String(cesu8::to_java_cesu8(v)),
+            _ => return Err(TagResolvingError::UnsupportedValue),
+        },
+        0x09 => {
+            let item_type = json
+                .get(ELEMENT_TYPE_KEY)
+                .and_then(Value::as_str)
+                .and_then(type_id)
+                .ok_or(TagResolvingError::UnsupportedValue)?;
+            let Value::Array(v) = v else {
+                return Err(TagResolvingError::UnsupportedValue);
+            };
+            let mut buf = Vec::with_capacity(v.len());
+            for v in v.iter() {
+                let v = resolve(v)?;
+                if u8::from(&v) != item_type {
+                    return Err(TagResolvingError::UnsupportedValue);
+                }
+                buf.push(v);
+            }
+            TagType::List(item_type, buf)
+        }
+        0x0a => {
+            let Value::Object(v) = v else {
+                return Err(TagResolvingError::UnsupportedValue);
+            };
+            let mut buf = IndexMap::with_capacity(v.len());
+            for (k, v) in v.iter() {
+                buf.insert(cesu8::to_java_cesu8(k), resolve(v)?);
+            }
+            TagType::Compound(buf)
+        }
+        0x0b => TagType::IntArray(resolve_array(v, resolve_integer)?),
+        0x0c => TagType::LongArray(resolve_array(v, resolve_long)?),
+        _ => return Err(TagResolvingError::UnsupportedValue),
+    })
+}
+
+/// Resolves the root entry of a typed document.
+pub(crate) fn resolve_entry<'a>(
+    nbt: &mut IndexMap<Cow<'a, [u8]>, TagType<'a>>,
+    k: &'a str,
+    v: &'a Value,
+) -> Result<(), TagResolvingError> {
+    nbt.insert(cesu8::to_java_cesu8(k), resolve(v)?);
+    Ok(())
+}
@@ -0,0 +1,494 @@
+use super::cesu8_to_string;
+use super::path;
+use super::path::PathSegment;
+use super::ByteArray;
+use super::IntArray;
+use super::LongArray;
+use super::TagResolvingError;
+use super::TagType;
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::ops::Index;
+use std::ops::IndexMut;
+
+/// An owned NBT tag with UTF-8 strings, for building and inspecting trees.
+///
+/// Converts from and to [`TagType`] with `try_from` and `from`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NbtValue {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// The item tag type and the items
+    List(u8, Vec<NbtValue>),
+    Compound(NbtCompound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// The entries of a TAG_Compound in their order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NbtCompound(IndexMap<String, NbtValue>);
+
+macro_rules! as_value {
+    ($name:ident, $variant:ident, $t:ty) => {
+        pub fn $name(&self) -> Option<$t> {
+            match self {
+                NbtValue::$variant(v) => Some(*v),
+                _ => None,
+            }
+        }
+    };
+    ($name:ident, $name_mut:ident, $variant:ident, $t:ty) => {
+        pub fn $name(&self) -> Option<&$t> {
+            match self {
+                NbtValue::$variant(v) => Some(v),
+                _ => None,
+            }
+        }
+
+        pub fn $name_mut(&mut self) -> Option<&mut $t> {
+            match self {
+                NbtValue::$variant(v) => Some(v),
+                _ => None,
+            }
+        }
+    };
+}
+
+impl NbtValue {
+    /// The tag type id, e.g. `0x03` for TAG_Int.
+    pub fn tag_type(&self) -> u8 {
+        match self {
+            NbtValue::Byte(_) => 0x01,
+            NbtValue::Short(_) => 0x02,
+            NbtValue::Int(_) => 0x03,
+            NbtValue::Long(_) => 0x04,
+            NbtValue::Float(_) => 0x05,
+            NbtValue::Double(_) => 0x06,
+            NbtValue::ByteArray(_) => 0x07,
+            NbtValue::String(_) => 0x08,
+            NbtValue::List(..) => 0x09,
+            NbtValue::Compound(_) => 0x0a,
+            NbtValue::IntArray(_) => 0x0b,
+            NbtValue::LongArray(_) => 0x0c,
+        }
+    }
+
+    as_value!(as_byte, Byte, i8);
+    as_value!(as_short, Short, i16);
+    as_value!(as_int, Int, i32);
+    as_value!(as_long, Long, i64);
+    as_value!(as_float, Float, f32);
+    as_value!(as_double, Double, f64);
+    as_value!(as_byte_array, as_byte_array_mut, ByteArray, Vec<i8>);
+    as_value!(as_compound, as_compound_mut, Compound, NbtCompound);
+    as_value!(as_int_array, as_int_array_mut, IntArray, Vec<i32>);
+    as_value!(as_long_array, as_long_array_mut, LongArray, Vec<i64>);
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[NbtValue]> {
+        match self {
+            NbtValue::List(_, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The items of a list, which must keep the item type of the list.
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<NbtValue>> {
+        match self {
+            NbtValue::List(_, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Gets an entry of a compound.
+    pub fn get(&self, name: &str) -> Option<&NbtValue> {
+        self.as_compound()?.get(name)
+    }
+
+    /// Gets an entry of a compound.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut NbtValue> {
+        self.as_compound_mut()?.get_mut(name)
+    }
+
+    /// Gets a nested tag by a path like `Data.Player.Pos[0]`.
+    pub fn get_path(&self, path: &str) -> Option<&NbtValue> {
+        let mut value = self;
+        for segment in path::parse(path)? {
+            value = match segment {
+                PathSegment::Name(name) => value.get(&name)?,
+                PathSegment::Index(i) => value.as_list()?.get(i)?,
+                PathSegment::AnyIndex => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Gets a nested tag by a path like `Data.Player.Pos[0]`.
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut NbtValue> {
+        self.get_segments_mut(&path::parse(path)?)
+    }
+
+    fn get_segments_mut(&mut self, segments: &[PathSegment]) -> Option<&mut NbtValue> {
+        let mut value = self;
+        for segment in segments {
+            value = match segment {
+                PathSegment::Name(name) => value.get_mut(name)?,
+                PathSegment::Index(i) => value.as_list_mut()?.get_mut(*i)?,
+                PathSegment::AnyIndex => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Sets a nested tag by a path like `Data.Player.Pos[0]`, inserting the entry into its
+    /// compound or replacing the item of its list. An index past the last item appends the
+    /// value, and the empty path replaces this tag.
+    ///
+    /// Returns the value back if the compound or list is missing, or the list holds another
+    /// item type.
+    pub fn set_path(&mut self, path: &str, value: impl Into<NbtValue>) -> Result<(), NbtValue> {
+        let value = value.into();
+        let Some(mut segments) = path::parse(path) else {
+            return Err(value);
+        };
+        let Some(last) = segments.pop() else {
+            *self = value;
+            return Ok(());
+        };
+        let Some(parent) = self.get_segments_mut(&segments) else {
+            return Err(value);
+        };
+        match (parent, last) {
+            (NbtValue::Compound(v), PathSegment::Name(name)) => {
+                v.insert(name, value);
+            }
+            // Empty lists take the type of their first item.
+            (NbtValue::List(item_type, v), PathSegment::Index(i))
+                if i <= v.len() && (v.is_empty() || *item_type == value.tag_type()) =>
+            {
+                *item_type = value.tag_type();
+                if i == v.len() {
+                    v.push(value);
+                } else {
+                    v[i] = value;
+                }
+            }
+            _ => return Err(value),
+        }
+        Ok(())
+    }
+}
+
+impl NbtCompound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NbtValue> {
+        self.0.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut NbtValue> {
+        self.0.get_mut(name)
+    }
+
+    /// Gets a nested tag by a path like `Data.Player.Pos[0]`, starting with an entry name.
+    pub fn get_path(&self, path: &str) -> Option<&NbtValue> {
+        let (name, rest) = split_path(path)?;
+        self.get(&name)?.get_path(rest)
+    }
+
+    /// Gets a nested tag by a path like `Data.Player.Pos[0]`, starting with an entry name.
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut NbtValue> {
+        let (name, rest) = split_path(path)?;
+        self.get_mut(&name)?.get_path_mut(rest)
+    }
+
+    /// Inserts an entry, keeping the position of an existing entry with the same name.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<NbtValue>,
+    ) -> Option<NbtValue> {
+        self.0.insert(name.into(), value.into())
+    }
+
+    /// Removes an entry, keeping the order of the others.
+    pub fn remove(&mut self, name: &str) -> Option<NbtValue> {
+        self.0.shift_remove(name)
+    }
+
+    pub fn iter(&self) -> indexmap::map::Iter<'_, String, NbtValue> {
+        self.0.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> indexmap::map::IterMut<'_, String, NbtValue> {
+        self.0.iter_mut()
+    }
+
+    pub fn keys(&self) -> indexmap::map::Keys<'_, String, NbtValue> {
+        self.0.keys()
+    }
+
+    pub fn values(&self) -> indexmap::map::Values<'_, String, NbtValue> {
+        self.0.values()
+    }
+}
+
+// Splits off the first entry name of a path, returning the rest to be resolved from that entry
+fn split_path(path: &str) -> Option<(String, &str)> {
+    let first = match path::parse(path)?.into_iter().next()? {
+        PathSegment::Name(name) => name,
+        PathSegment::Index(_) | PathSegment::AnyIndex => return None,
+    };
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in path.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '.' | '[' if !quoted => {
+                let rest = &path[i..];
+                return Some((first, rest.strip_prefix('.').unwrap_or(rest)));
+            }
+            _ => {}
+        }
+    }
+    Some((first, ""))
+}
+
+impl Index<&str> for NbtCompound {
+    type Output = NbtValue;
+
+    /// Panics if there is no entry with the name.
+    fn index(&self, name: &str) -> &NbtValue {
+        match self.get(name) {
+            Some(v) => v,
+            None => panic!("no entry named {:?}", name),
+        }
+    }
+}
+
+impl IndexMut<&str> for NbtCompound {
+    fn index_mut(&mut self, name: &str) -> &mut NbtValue {
+        match self.get_mut(name) {
+            Some(v) => v,
+            None => panic!("no entry named {:?}", name),
+        }
+    }
+}
+
+impl Index<&str> for NbtValue {
+    type Output = NbtValue;
+
+    /// Panics if this is not a compound or there is no entry with the name.
+    fn index(&self, name: &str) -> &NbtValue {
+        match self {
+            NbtValue::Compound(v) => &v[name],
+            _ => panic!("not a compound"),
+        }
+    }
+}
+
+impl IndexMut<&str> for NbtValue {
+    fn index_mut(&mut self, name: &str) -> &mut NbtValue {
+        match self {
+            NbtValue::Compound(v) => &mut v[name],
+            _ => panic!("not a compound"),
+        }
+    }
+}
+
+impl Index<usize> for NbtValue {
+    type Output = NbtValue;
+
+    /// Panics if this is not a list or the index is out of bounds.
+    fn index(&self, i: usize) -> &NbtValue {
+        match self {
+            NbtValue::List(_, v) => &v[i],
+            _ => panic!("not a list"),
+        }
+    }
+}
+
+impl IndexMut<usize> for NbtValue {
+    fn index_mut(&mut self, i: usize) -> &mut NbtValue {
+        match self {
+            NbtValue::List(_, v) => &mut v[i],
+            _ => panic!("not a list"),
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<NbtValue>> FromIterator<(K, V)> for NbtCompound {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl IntoIterator for NbtCompound {
+    type Item = (String, NbtValue);
+    type IntoIter = indexmap::map::IntoIter<String, NbtValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a NbtCompound {
+    type Item = (&'a String, &'a NbtValue);
+    type IntoIter = indexmap::map::Iter<'a, String, NbtValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+macro_rules! from_value {
+    ($t:ty, $variant:ident) => {
+        impl From<$t> for NbtValue {
+            fn from(v: $t) -> Self {
+                NbtValue::$variant(v.into())
+            }
+        }
+    };
+}
+
+from_value!(i8, Byte);
+from_value!(bool, Byte);
+from_value!(i16, Short);
+from_value!(i32, Int);
+from_value!(i64, Long);
+from_value!(f32, Float);
+from_value!(f64, Double);
+from_value!(String, String);
+from_value!(&str, String);
+from_value!(NbtCompound, Compound);
+
+impl From<ByteArray> for NbtValue {
+    fn from(v: ByteArray) -> Self {
+        NbtValue::ByteArray(v.0)
+    }
+}
+
+impl From<IntArray> for NbtValue {
+    fn from(v: IntArray) -> Self {
+        NbtValue::IntArray(v.0)
+    }
+}
+
+impl From<LongArray> for NbtValue {
+    fn from(v: LongArray) -> Self {
+        NbtValue::LongArray(v.0)
+    }
+}
+
+/// A list with the type of its first item, or TAG_End if empty.
+impl<T: Into<NbtValue>> From<Vec<T>> for NbtValue {
+    fn from(v: Vec<T>) -> Self {
+        let items: Vec<NbtValue> = v.into_iter().map(Into::into).collect();
+        let item_type = items.first().map_or(0x00, NbtValue::tag_type);
+        NbtValue::List(item_type, items)
+    }
+}
+
+/// Fails on TAG_End, which is not a value.
+impl TryFrom<TagType<'_>> for NbtValue {
+    type Error = TagResolvingError;
+
+    fn try_from(value: TagType<'_>) -> Result<Self, Self::Error> {
+        Ok(match value {
+            TagType::End => return Err(TagResolvingError::UnsupportedValue),
+            TagType::Byte(v) => NbtValue::Byte(v),
+            TagType::Short(v) => NbtValue::Short(v),
+            TagType::Int(v) => NbtValue::Int(v),
+            TagType::Long(v) => NbtValue::Long(v.into()),
+            TagType::Float(v) => NbtValue::Float(v),
+            TagType::Double(v) => NbtValue::Double(v),
+            TagType::ByteArray(v) => NbtValue::ByteArray(v),
+            TagType::String(v) => NbtValue::String(cesu8_to_string(&v)),
+            TagType::List(item_type, v) => NbtValue::List(
+                item_type,
+                v.into_iter()
+                    .map(NbtValue::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            TagType::Compound(v) => NbtValue::Compound(NbtCompound::try_from(v)?),
+            TagType::IntArray(v) => NbtValue::IntArray(v),
+            TagType::LongArray(v) => NbtValue::LongArray(v.into_iter().map(i64::from).collect()),
+        })
+    }
+}
+
+/// Also converts the root tag returned by [`read`](super::read), keyed by the root name.
+impl<'a> TryFrom<IndexMap<Cow<'a, [u8]>, TagType<'a>>> for NbtCompound {
+    type Error = TagResolvingError;
+
+    fn try_from(value: IndexMap<Cow<'a, [u8]>, TagType<'a>>) -> Result<Self, Self::Error> {
+        value
+            .into_iter()
+            .map(|(k, v)| Ok((cesu8_to_string(&k), NbtValue::try_from(v)?)))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl From<NbtValue> for TagType<'static> {
+    fn from(value: NbtValue) -> Self {
+        match value {
+            NbtValue::Byte(v) => TagType::Byte(v),
+            NbtValue::Short(v) => TagType::Short(v),
+            NbtValue::Int(v) => TagType::Int(v),
+            NbtValue::Long(v) => TagType::Long(v.into()),
+            NbtValue::Float(v) => TagType::Float(v),
+            NbtValue::Double(v) => TagType::Double(v),
+            NbtValue::ByteArray(v) => TagType::ByteArray(v),
+            NbtValue::String(v) => TagType::String(cesu8::to_java_cesu8(&v).into_owned().into()),
+            NbtValue::List(item_type, v) => {
+                TagType::List(item_type, v.into_iter().map(TagType::from).collect())
+            }
+            NbtValue::Compound(v) => TagType::Compound(v.into()),
+            NbtValue::IntArray(v) => TagType::IntArray(v),
+            NbtValue::LongArray(v) => TagType::LongArray(v.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl From<NbtCompound> for IndexMap<Cow<'static, [u8]>, TagType<'static>> {
+    fn from(value: NbtCompound) -> Self {
+        value
+            .into_iter()
+            .map(|(k, v)| (cesu8::to_java_cesu8(&k).into_owned().into(), v.into()))
+            .collect()
+    }
+}
@@ -0,0 +1,374 @@
+use super::Endianness;
+use super::LongNumber;
+use super::NbtMode;
+use super::TagType;
+use bytes::BufMut;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::Write;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NbtEncodeError {
+    Io(io::ErrorKind),
+    MultipleRoots,
+    UnexpectedEnd,
+    UnclosedTag,
+    InvalidTag,
+    InvalidListItemType(u8),
+    ListItemTypeMismatch { expected: u8, actual: u8 },
+    ListLengthMismatch { expected: usize, actual: usize },
+    TooLong(usize),
+}
+
+impl Display for NbtEncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NbtEncodeError::Io(kind) => write!(f, "Failed to write: {}.", kind),
+            NbtEncodeError::MultipleRoots => write!(f, "Only one root tag can be written."),
+            NbtEncodeError::UnexpectedEnd => write!(f, "No compound or list to end."),
+            NbtEncodeError::UnclosedTag => write!(f, "A compound or list is not ended."),
+            NbtEncodeError::InvalidTag => write!(f, "TAG_End cannot be written as a tag."),
+            NbtEncodeError::InvalidListItemType(tag_type) => {
+                write!(f, "Invalid list item type 0x{:02x}.", tag_type)
+            }
+            NbtEncodeError::ListItemTypeMismatch { expected, actual } => write!(
+                f,
+                "List item of type 0x{:02x} in a list of type 0x{:02x}.",
+                actual, expected
+            ),
+            NbtEncodeError::ListLengthMismatch { expected, actual } => write!(
+                f,
+                "List of length {} ended after {} items.",
+                expected, actual
+            ),
+            NbtEncodeError::TooLong(len) => write!(f, "Length {} is too long.", len),
+        }
+    }
+}
+
+impl Error for NbtEncodeError {}
+
+impl From<io::Error> for NbtEncodeError {
+    fn from(err: io::Error) -> Self {
+        NbtEncodeError::Io(err.kind())
+    }
+}
+
+enum Frame {
+    Compound,
+    List {
+        item_type: u8,
+        len: usize,
+        written: usize,
+    },
+}
+
+/// A writer of uncompressed binary NBT, which writes the tags one at a time to an [`io::Write`].
+///
+/// Compounds and lists are opened with [`begin_compound`](Self::begin_compound) and
+/// [`begin_list`](Self::begin_list) and closed with [`end`](Self::end). The structure is
+/// validated as it is written, e.g. the items of a list must match its item type and length.
+/// The names of list items are ignored.
+pub struct NbtWriter<W: Write> {
+    writer: W,
+    stack: Vec<Frame>,
+    endianness: Endianness,
+    mode: NbtMode,
+    has_root: bool,
+}
+
+macro_rules! put_number {
+    ($name:ident, $t:ty) => {
+        fn $name(&mut self, n: $t) -> Result<(), NbtEncodeError> {
+            match self.endianness {
+                Endianness::Big => self.put_slice(&n.to_be_bytes()),
+                Endianness::Little => self.put_slice(&n.to_le_bytes()),
+            }
+        }
+    };
+}
+
+macro_rules! write_value {
+    ($name:ident, $put:ident, $t:ty, $tag_type:expr) => {
+        pub fn $name(&mut self, name: &str, v: $t) -> Result<(), NbtEncodeError> {
+            self.begin_tag(&cesu8::to_java_cesu8(name), $tag_type)?;
+            self.$put(v)
+        }
+    };
+}
+
+impl<W: Write> NbtWriter<W> {
+    pub fn new(writer: W, endianness: Endianness, mode: NbtMode) -> Self {
+        Self {
+            writer,
+            stack: vec![],
+            endianness,
+            mode,
+            has_root: false,
+        }
+    }
+
+    /// Checks that every compound and list has been ended, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, NbtEncodeError> {
+        if !self.stack.is_empty() {
+            return Err(NbtEncodeError::UnclosedTag);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    put_number!(put_u8, u8);
+    put_number!(put_i8, i8);
+    put_number!(put_i16, i16);
+    put_number!(put_f32, f32);
+    put_number!(put_f64, f64);
+
+    fn put_slice(&mut self, src: &[u8]) -> Result<(), NbtEncodeError> {
+        self.writer.write_all(src)?;
+        Ok(())
+    }
+
+    // Bedrock Edition network NBT
+    fn is_varint(&self) -> bool {
+        self.endianness == Endianness::Little && self.mode == NbtMode::Network
+    }
+
+    fn put_varint(&mut self, mut n: u64) -> Result<(), NbtEncodeError> {
+        let mut buf = vec![];
+        while 0x80 <= n {
+            buf.put_u8(n as u8 | 0x80);
+            n >>= 7;
+        }
+        buf.put_u8(n as u8);
+        self.put_slice(&buf)
+    }
+
+    fn put_i32(&mut self, n: i32) -> Result<(), NbtEncodeError> {
+        if self.is_varint() {
+            return self.put_varint(((n << 1) ^ (n >> 31)) as u32 as u64);
+        }
+        match self.endianness {
+            Endianness::Big => self.put_slice(&n.to_be_bytes()),
+            Endianness::Little => self.put_slice(&n.to_le_bytes()),
+        }
+    }
+
+    fn put_i64(&mut self, n: i64) -> Result<(), NbtEncodeError> {
+        if self.is_varint() {
+            return self.put_varint(((n << 1) ^ (n >> 63)) as u64);
+        }
+        match self.endianness {
+            Endianness::Big => self.put_slice(&n.to_be_bytes()),
+            Endianness::Little => self.put_slice(&n.to_le_bytes()),
+        }
+    }
+
+    fn put_long(&mut self, n: &LongNumber) -> Result<(), NbtEncodeError> {
+        self.put_i64(n.clone().into())
+    }
+
+    /// Writes the i32 length prefix of arrays and lists.
+    fn put_len(&mut self, len: usize) -> Result<(), NbtEncodeError> {
+        match i32::try_from(len) {
+            Ok(len) => self.put_i32(len),
+            Err(_) => Err(NbtEncodeError::TooLong(len)),
+        }
+    }
+
+    /// Writes a length-prefixed CESU-8 string, as used by tag names and TAG_String.
+    fn put_string(&mut self, v: &[u8]) -> Result<(), NbtEncodeError> {
+        if self.is_varint() {
+            self.put_varint(v.len() as u64)?;
+        } else {
+            let len = u16::try_from(v.len()).map_err(|_| NbtEncodeError::TooLong(v.len()))?;
+            match self.endianness {
+                Endianness::Big => self.put_slice(&len.to_be_bytes())?,
+                Endianness::Little => self.put_slice(&len.to_le_bytes())?,
+            }
+        }
+        self.put_slice(v)
+    }
+
+    fn put_byte_array(&mut self, v: &[i8]) -> Result<(), NbtEncodeError> {
+        self.put_len(v.len())?;
+        let buf = v.iter().map(|v| *v as u8).collect::<Vec<_>>();
+        self.put_slice(&buf)
+    }
+
+    fn put_int_array(&mut self, v: &[i32]) -> Result<(), NbtEncodeError> {
+        self.put_len(v.len())?;
+        v.iter().try_for_each(|v| self.put_i32(*v))
+    }
+
+    fn put_long_array(&mut self, v: &[LongNumber]) -> Result<(), NbtEncodeError> {
+        self.put_len(v.len())?;
+        v.iter().try_for_each(|v| self.put_long(v))
+    }
+
+    /// Validates a tag at the current position and writes its type and name where needed.
+    fn begin_tag(&mut self, name: &[u8], tag_type: u8) -> Result<(), NbtEncodeError> {
+        if tag_type == 0x00 {
+            return Err(NbtEncodeError::InvalidTag);
+        }
+        match self.stack.last_mut() {
+            None => {
+                if self.has_root {
+                    return Err(NbtEncodeError::MultipleRoots);
+                }
+                self.has_root = true;
+                self.put_u8(tag_type)?;
+                // Java Edition network NBT omits the name of the root tag.
+                if !(self.endianness == Endianness::Big && self.mode == NbtMode::Network) {
+                    self.put_string(name)?;
+                }
+            }
+            Some(Frame::Compound) => {
+                self.put_u8(tag_type)?;
+                self.put_string(name)?;
+            }
+            Some(Frame::List {
+                item_type,
+                len,
+                written,
+            }) => {
+                if tag_type != *item_type {
+                    return Err(NbtEncodeError::ListItemTypeMismatch {
+                        expected: *item_type,
+                        actual: tag_type,
+                    });
+                }
+                if written == len {
+                    return Err(NbtEncodeError::ListLengthMismatch {
+                        expected: *len,
+                        actual: *written + 1,
+                    });
+                }
+                *written += 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn begin_compound(&mut self, name: &str) -> Result<(), NbtEncodeError> {
+        self.begin_compound_raw(&cesu8::to_java_cesu8(name))
+    }
+
+    fn begin_compound_raw(&mut self, name: &[u8]) -> Result<(), NbtEncodeError> {
+        self.begin_tag(name, 0x0a)?;
+        self.stack.push(Frame::Compound);
+        Ok(())
+    }
+
+    /// Begins a list of `len` items of `item_type`, which is `0x00` (TAG_End) only for empty lists.
+    pub fn begin_list(
+        &mut self,
+        name: &str,
+        item_type: u8,
+        len: usize,
+    ) -> Result<(), NbtEncodeError> {
+        self.begin_list_raw(&cesu8::to_java_cesu8(name), item_type, len)
+    }
+
+    fn begin_list_raw(
+        &mut self,
+        name: &[u8],
+        item_type: u8,
+        len: usize,
+    ) -> Result<(), NbtEncodeError> {
+        if TagType::u8_to_str(item_type).is_err() || (item_type == 0x00 && len != 0) {
+            return Err(NbtEncodeError::InvalidListItemType(item_type));
+        }
+        self.begin_tag(name, 0x09)?;
+        self.put_u8(item_type)?;
+        self.put_len(len)?;
+        self.stack.push(Frame::List {
+            item_type,
+            len,
+            written: 0,
+        });
+        Ok(())
+    }
+
+    /// Ends the innermost compound or list.
+    pub fn end(&mut self) -> Result<(), NbtEncodeError> {
+        match self.stack.pop() {
+            None => Err(NbtEncodeError::UnexpectedEnd),
+            Some(Frame::Compound) => self.put_u8(0x00),
+            Some(Frame::List { len, written, .. }) if len != written => {
+                Err(NbtEncodeError::ListLengthMismatch {
+                    expected: len,
+                    actual: written,
+                })
+            }
+            Some(Frame::List { .. }) => Ok(()),
+        }
+    }
+
+    write_value!(write_byte, put_i8, i8, 0x01);
+    write_value!(write_short, put_i16, i16, 0x02);
+    write_value!(write_int, put_i32, i32, 0x03);
+    write_value!(write_long, put_i64, i64, 0x04);
+    write_value!(write_float, put_f32, f32, 0x05);
+    write_value!(write_double, put_f64, f64, 0x06);
+    write_value!(write_byte_array, put_byte_array, &[i8], 0x07);
+    write_value!(write_int_array, put_int_array, &[i32], 0x0b);
+
+    pub fn write_string(&mut self, name: &str, v: &str) -> Result<(), NbtEncodeError> {
+        self.begin_tag(&cesu8::to_java_cesu8(name), 0x08)?;
+        self.put_string(&cesu8::to_java_cesu8(v))
+    }
+
+    pub fn write_long_array(&mut self, name: &str, v: &[i64]) -> Result<(), NbtEncodeError> {
+        self.begin_tag(&cesu8::to_java_cesu8(name), 0x0c)?;
+        self.put_len(v.len())?;
+        v.iter().try_for_each(|v| self.put_i64(*v))
+    }
+
+    /// Writes a whole tag, including the entries of compounds and the items of lists.
+    pub fn write_tag(&mut self, name: &str, tag: &TagType) -> Result<(), NbtEncodeError> {
+        self.write_tag_raw(&cesu8::to_java_cesu8(name), tag)
+    }
+
+    /// Like [`write_tag`](Self::write_tag), with the name in CESU-8 as stored in [`TagType`].
+    pub(crate) fn write_tag_raw(
+        &mut self,
+        name: &[u8],
+        tag: &TagType,
+    ) -> Result<(), NbtEncodeError> {
+        match tag {
+            TagType::List(item_type, v) => {
+                self.begin_list_raw(name, *item_type, v.len())?;
+                for v in v.iter() {
+                    self.write_tag_raw(b"", v)?;
+                }
+                return self.end();
+            }
+            TagType::Compound(v) => {
+                self.begin_compound_raw(name)?;
+                for (name, v) in v.iter() {
+                    self.write_tag_raw(name, v)?;
+                }
+                return self.end();
+            }
+            _ => {}
+        }
+        self.begin_tag(name, u8::from(tag))?;
+        match tag {
+            TagType::Byte(v) => self.put_i8(*v),
+            TagType::Short(v) => self.put_i16(*v),
+            TagType::Int(v) => self.put_i32(*v),
+            TagType::Long(v) => self.put_long(v),
+            TagType::Float(v) => self.put_f32(*v),
+            TagType::Double(v) => self.put_f64(*v),
+            TagType::ByteArray(v) => self.put_byte_array(v),
+            TagType::String(v) => self.put_string(v),
+            TagType::IntArray(v) => self.put_int_array(v),
+            TagType::LongArray(v) => self.put_long_array(v),
+            // Rejected by begin_tag, or written above
+            TagType::End | TagType::List(..) | TagType::Compound(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::nbt;
+use crate::nbt::CompressionOptions;
+use crate::nbt::Endianness;
+use crate::nbt::FileType;
+use crate::nbt::NbtDecodeError;
+use crate::nbt::NbtMode;
+use crate::nbt::TagType;
+use bytes::Buf;
+use bytes::BufMut;
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+
+pub const SECTOR_SIZE: usize = 4096;
+pub const REGION_WIDTH: u8 = 32;
+
+const CHUNK_COUNT: usize = REGION_WIDTH as usize * REGION_WIDTH as usize;
+const HEADER_SECTORS: usize = 2;
+const MAX_CHUNK_SECTORS: usize = 0xff;
+const EXTERNAL_CHUNK_FLAG: u8 = 0x80;
+
+/// A chunk stored in a region file.
+///
+/// `x` and `z` are the coordinates local to the region (0 to 31) and `nbt` holds the decompressed
+/// NBT, which is compressed again with `file_type` when the region is written.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Chunk {
+    pub x: u8,
+    pub z: u8,
+    pub timestamp: u32,
+    pub file_type: FileType,
+    pub nbt: Vec<u8>,
+}
+
+impl Chunk {
+    pub fn decode(&self) -> Result<IndexMap<Cow<'static, [u8]>, TagType<'static>>, NbtDecodeError> {
+        nbt::decode(&self.nbt, Endianness::Big, NbtMode::File)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Region {
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegionError {
+    TruncatedHeader,
+    InvalidChunkLocation { x: u8, z: u8 },
+    ExternalChunk { x: u8, z: u8 },
+    UnsupportedCompression { x: u8, z: u8, compression: u8 },
+    DecompressionFailed { x: u8, z: u8, kind: io::ErrorKind },
+    InvalidCoordinates { x: u8, z: u8 },
+    DuplicateChunk { x: u8, z: u8 },
+    ChunkTooLarge { x: u8, z: u8 },
+}
+
+impl Display for RegionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionError::TruncatedHeader => write!(f, "Truncated region header."),
+            RegionError::InvalidChunkLocation { x, z } => {
+                write!(f, "Invalid location of chunk {}, {}.", x, z)
+            }
+            RegionError::ExternalChunk { x, z } => {
+                write!(f, "Chunk {}, {} is stored in an external file.", x, z)
+            }
+            RegionError::UnsupportedCompression { x, z, compression } => write!(
+                f,
+                "Unsupported compression type {} of chunk {}, {}.",
+                compression, x, z
+            ),
+            RegionError::DecompressionFailed { x, z, kind } => {
+                write!(f, "Failed to decompress chunk {}, {}: {}.", x, z, kind)
+            }
+            RegionError::InvalidCoordinates { x, z } => {
+                write!(f, "Invalid chunk coordinates {}, {}.", x, z)
+            }
+            RegionError::DuplicateChunk { x, z } => write!(f, "Duplicate chunk {}, {}.", x, z),
+            RegionError::ChunkTooLarge { x, z } => write!(f, "Chunk {}, {} is too large.", x, z),
+        }
+    }
+}
+
+impl Error for RegionError {}
+
+fn compression_to_file_type(compression: u8) -> Option<FileType> {
+    match compression {
+        1 => Some(FileType::GZip),
+        2 => Some(FileType::Zlib),
+        3 => Some(FileType::Raw),
+        4 => Some(FileType::Lz4),
+        _ => None,
+    }
+}
+
+fn file_type_to_compression(file_type: FileType) -> u8 {
+    match file_type {
+        FileType::GZip => 1,
+        FileType::Zlib => 2,
+        FileType::Raw => 3,
+        FileType::Lz4 => 4,
+    }
+}
+
+/// Parses an Anvil (`.mca`) or McRegion (`.mcr`) region file and decompresses its chunks.
+pub fn read(region: &[u8]) -> Result<Region, RegionError> {
+    let mut chunks = vec![];
+    if region.is_empty() {
+        return Ok(Region { chunks });
+    }
+    if region.len() < HEADER_SECTORS * SECTOR_SIZE {
+        return Err(RegionError::TruncatedHeader);
+    }
+    let mut locations = &region[..SECTOR_SIZE];
+    let mut timestamps = &region[SECTOR_SIZE..HEADER_SECTORS * SECTOR_SIZE];
+    for i in 0..CHUNK_COUNT {
+        let x = (i % REGION_WIDTH as usize) as u8;
+        let z = (i / REGION_WIDTH as usize) as u8;
+        let location = locations.get_u32();
+        let timestamp = timestamps.get_u32();
+        if location == 0 {
+            continue;
+        }
+        let offset = (location >> 8) as usize * SECTOR_SIZE;
+        let sectors = (location & 0xff) as usize;
+        if offset < HEADER_SECTORS * SECTOR_SIZE || region.len() < offset + 5 {
+            return Err(RegionError::InvalidChunkLocation { x, z });
+        }
+        let mut data = &region[offset..];
+        let len = data.get_u32() as usize;
+        if len == 0 || data.len() < len || sectors * SECTOR_SIZE < len + 4 {
+            return Err(RegionError::InvalidChunkLocation { x, z });
+        }
+        let compression = data.get_u8();
+        if compression & EXTERNAL_CHUNK_FLAG != 0 {
+            return Err(RegionError::ExternalChunk { x, z });
+        }
+        let file_type = match compression_to_file_type(compression) {
+            Some(file_type) => file_type,
+            None => return Err(RegionError::UnsupportedCompression { x, z, compression }),
+        };
+        let nbt = match file_type.decompress(data[..len - 1].to_vec()) {
+            Ok(nbt) => nbt,
+            Err(err) => {
+                return Err(RegionError::DecompressionFailed {
+                    x,
+                    z,
+                    kind: err.kind(),
+                })
+            }
+        };
+        chunks.push(Chunk {
+            x,
+            z,
+            timestamp,
+            file_type,
+            nbt,
+        });
+    }
+    Ok(Region { chunks })
+}
+
+/// Compresses the chunks of a region and lays them out into a region file.
+pub fn write(region: &Region) -> Result<Vec<u8>, RegionError> {
+    write_with(region, &CompressionOptions::default())
+}
+
+/// Lays out a region like [`write`], compressing the chunks with `options`.
+pub fn write_with(region: &Region, options: &CompressionOptions) -> Result<Vec<u8>, RegionError> {
+    let mut chunks: Vec<Option<&Chunk>> = vec![None; CHUNK_COUNT];
+    for chunk in region.chunks.iter() {
+        let (x, z) = (chunk.x, chunk.z);
+        if REGION_WIDTH <= x || REGION_WIDTH <= z {
+            return Err(RegionError::InvalidCoordinates { x, z });
+        }
+        let i = x as usize + z as usize * REGION_WIDTH as usize;
+        if chunks[i].is_some() {
+            return Err(RegionError::DuplicateChunk { x, z });
+        }
+        chunks[i] = Some(chunk);
+    }
+    let mut locations = Vec::with_capacity(SECTOR_SIZE);
+    let mut timestamps = Vec::with_capacity(SECTOR_SIZE);
+    let mut body = vec![];
+    for chunk in chunks {
+        let Some(chunk) = chunk else {
+            locations.put_u32(0);
+            timestamps.put_u32(0);
+            continue;
+        };
+        let data = chunk.file_type.compress_with(chunk.nbt.clone(), options);
+        let sectors = (data.len() + 5).div_ceil(SECTOR_SIZE);
+        if MAX_CHUNK_SECTORS < sectors {
+            return Err(RegionError::ChunkTooLarge {
+                x: chunk.x,
+                z: chunk.z,
+            });
+        }
+        let offset = HEADER_SECTORS + body.len() / SECTOR_SIZE;
+        locations.put_u32((offset as u32) << 8 | sectors as u32);
+        timestamps.put_u32(chunk.timestamp);
+        body.put_u32(data.len() as u32 + 1);
+        body.put_u8(file_type_to_compression(chunk.file_type));
+        body.put_slice(&data);
+        body.resize((offset - HEADER_SECTORS + sectors) * SECTOR_SIZE, 0);
+    }
+    let mut buf = locations;
+    buf.append(&mut timestamps);
+    buf.append(&mut body);
+    Ok(buf)
+}
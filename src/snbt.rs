@@ -0,0 +1,501 @@
+use crate::nbt::cesu8_to_string;
+use crate::nbt::f32_from_non_finite_str;
+use crate::nbt::f64_from_non_finite_str;
+use crate::nbt::LongNumber;
+use crate::nbt::TagType;
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SnbtParseErrorKind {
+    UnexpectedEof,
+    UnexpectedChar(char),
+    ExpectedKey,
+    ExpectedValue,
+    InvalidEscape,
+    MixedListTypes,
+    InvalidArrayItem,
+    TrailingData,
+}
+
+impl Display for SnbtParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SnbtParseErrorKind::UnexpectedEof => write!(f, "Unexpected end of SNBT."),
+            SnbtParseErrorKind::UnexpectedChar(c) => write!(f, "Unexpected character '{}'.", c),
+            SnbtParseErrorKind::ExpectedKey => write!(f, "Expected a key."),
+            SnbtParseErrorKind::ExpectedValue => write!(f, "Expected a value."),
+            SnbtParseErrorKind::InvalidEscape => write!(f, "Invalid escape sequence."),
+            SnbtParseErrorKind::MixedListTypes => write!(f, "List items must have the same type."),
+            SnbtParseErrorKind::InvalidArrayItem => write!(f, "Invalid array item type."),
+            SnbtParseErrorKind::TrailingData => write!(f, "Trailing data after the root tag."),
+        }
+    }
+}
+
+/// An error raised while parsing SNBT, `offset` being the byte offset into the SNBT.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SnbtParseError {
+    pub offset: usize,
+    pub kind: SnbtParseErrorKind,
+}
+
+impl Display for SnbtParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (offset: {})", self.kind, self.offset)
+    }
+}
+
+impl Error for SnbtParseError {}
+
+/// Parses SNBT (e.g. `{Count:1b,id:"minecraft:stone",Pos:[1.0d,2.0d]}`) into a tag.
+///
+/// Like Minecraft, an unquoted value that is not a valid number is read as a string.
+pub fn parse(snbt: &str) -> Result<TagType<'static>, SnbtParseError> {
+    let mut parser = Parser { snbt, pos: 0 };
+    let tag = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < snbt.len() {
+        return Err(parser.error(SnbtParseErrorKind::TrailingData));
+    }
+    Ok(tag)
+}
+
+struct Parser<'s> {
+    snbt: &'s str,
+    pos: usize,
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+impl Parser<'_> {
+    fn error(&self, kind: SnbtParseErrorKind) -> SnbtParseError {
+        SnbtParseError {
+            offset: self.pos,
+            kind,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.snbt[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Result<char, SnbtParseError> {
+        match self.peek() {
+            Some(c) => {
+                self.pos += c.len_utf8();
+                Ok(c)
+            }
+            None => Err(self.error(SnbtParseErrorKind::UnexpectedEof)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SnbtParseError> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(SnbtParseError {
+                offset: pos,
+                kind: SnbtParseErrorKind::UnexpectedChar(c),
+            }),
+        }
+    }
+
+    fn parse_unquoted(&mut self) -> &str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !is_unquoted_char(c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.snbt[start..self.pos]
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, SnbtParseError> {
+        let quote = self.next()?;
+        let mut buf = String::new();
+        loop {
+            match self.next()? {
+                c if c == quote => return Ok(buf),
+                '\\' => {
+                    let pos = self.pos;
+                    let c = match self.next()? {
+                        c @ ('\\' | '"' | '\'') => c,
+                        'b' => '\x08',
+                        'f' => '\x0c',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let hex = self.snbt.get(self.pos..self.pos + 4);
+                            let c = hex
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32);
+                            match c {
+                                Some(c) => {
+                                    self.pos += 4;
+                                    c
+                                }
+                                None => {
+                                    return Err(SnbtParseError {
+                                        offset: pos,
+                                        kind: SnbtParseErrorKind::InvalidEscape,
+                                    })
+                                }
+                            }
+                        }
+                        _ => {
+                            return Err(SnbtParseError {
+                                offset: pos,
+                                kind: SnbtParseErrorKind::InvalidEscape,
+                            })
+                        }
+                    };
+                    buf.push(c);
+                }
+                c => buf.push(c),
+            }
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<String, SnbtParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"' | '\'') => self.parse_quoted(),
+            _ => match self.parse_unquoted() {
+                "" => Err(self.error(SnbtParseErrorKind::ExpectedKey)),
+                key => Ok(key.to_string()),
+            },
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<TagType<'static>, SnbtParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_compound(),
+            Some('[') => self.parse_list(),
+            Some('"' | '\'') => Ok(string_tag(&self.parse_quoted()?)),
+            _ => match self.parse_unquoted() {
+                "" => Err(self.error(SnbtParseErrorKind::ExpectedValue)),
+                literal => Ok(parse_literal(literal)),
+            },
+        }
+    }
+
+    fn parse_compound(&mut self) -> Result<TagType<'static>, SnbtParseError> {
+        self.expect('{')?;
+        let mut buf = IndexMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(TagType::Compound(buf));
+        }
+        loop {
+            let key = self.parse_key()?;
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            buf.insert(Cow::Owned(cesu8::to_java_cesu8(&key).into_owned()), value);
+            if !self.parse_separator('}')? {
+                return Ok(TagType::Compound(buf));
+            }
+        }
+    }
+
+    // Return: whether another item follows
+    fn parse_separator(&mut self, end: char) -> Result<bool, SnbtParseError> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        match self.next()? {
+            ',' => Ok(true),
+            c if c == end => Ok(false),
+            c => Err(SnbtParseError {
+                offset: pos,
+                kind: SnbtParseErrorKind::UnexpectedChar(c),
+            }),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<TagType<'static>, SnbtParseError> {
+        self.expect('[')?;
+        let rest = &self.snbt[self.pos..];
+        let array_type = match rest.as_bytes() {
+            [t @ (b'B' | b'I' | b'L'), b';', ..] => Some(*t),
+            _ => None,
+        };
+        if array_type.is_some() {
+            self.pos += 2;
+        }
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        } else {
+            loop {
+                let pos = self.pos;
+                let item = self.parse_value()?;
+                if let Some(first) = items.first() {
+                    if u8::from(first) != u8::from(&item) {
+                        return Err(SnbtParseError {
+                            offset: pos,
+                            kind: SnbtParseErrorKind::MixedListTypes,
+                        });
+                    }
+                }
+                if array_type.is_some() && !is_array_item(array_type, &item) {
+                    return Err(SnbtParseError {
+                        offset: pos,
+                        kind: SnbtParseErrorKind::InvalidArrayItem,
+                    });
+                }
+                items.push(item);
+                if !self.parse_separator(']')? {
+                    break;
+                }
+            }
+        }
+        let tag = match array_type {
+            Some(b'B') => TagType::ByteArray(
+                items
+                    .into_iter()
+                    .filter_map(|v| match v {
+                        TagType::Byte(v) => Some(v),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            Some(b'I') => TagType::IntArray(
+                items
+                    .into_iter()
+                    .filter_map(|v| match v {
+                        TagType::Int(v) => Some(v),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            Some(_) => TagType::LongArray(
+                items
+                    .into_iter()
+                    .filter_map(|v| match v {
+                        TagType::Long(v) => Some(v),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            None => {
+                let item_type = items.first().map_or(0x00, u8::from);
+                TagType::List(item_type, items)
+            }
+        };
+        Ok(tag)
+    }
+}
+
+fn is_array_item(array_type: Option<u8>, item: &TagType) -> bool {
+    matches!(
+        (array_type, item),
+        (Some(b'B'), TagType::Byte(_))
+            | (Some(b'I'), TagType::Int(_))
+            | (Some(b'L'), TagType::Long(_))
+    )
+}
+
+fn string_tag(v: &str) -> TagType<'static> {
+    TagType::String(Cow::Owned(cesu8::to_java_cesu8(v).into_owned()))
+}
+
+fn is_integer(v: &str) -> bool {
+    let v = v.strip_prefix(['-', '+']).unwrap_or(v);
+    v == "0" || (!v.is_empty() && !v.starts_with('0') && v.bytes().all(|c| c.is_ascii_digit()))
+}
+
+// needs_dot: whether a decimal point is required, which is the case for doubles without suffix
+fn is_decimal(v: &str, needs_dot: bool) -> bool {
+    let v = v.strip_prefix(['-', '+']).unwrap_or(v);
+    let (mantissa, exponent) = match v.find(['e', 'E']) {
+        Some(i) => (&v[..i], Some(&v[i + 1..])),
+        None => (v, None),
+    };
+    let valid_mantissa = match mantissa.split_once('.') {
+        Some((int, frac)) => {
+            (!int.is_empty() || !frac.is_empty())
+                && int.bytes().all(|c| c.is_ascii_digit())
+                && frac.bytes().all(|c| c.is_ascii_digit())
+        }
+        None => !needs_dot && !mantissa.is_empty() && mantissa.bytes().all(|c| c.is_ascii_digit()),
+    };
+    let valid_exponent = match exponent {
+        Some(exponent) => {
+            let exponent = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+            !exponent.is_empty() && exponent.bytes().all(|c| c.is_ascii_digit())
+        }
+        None => true,
+    };
+    valid_mantissa && valid_exponent
+}
+
+fn parse_literal(v: &str) -> TagType<'static> {
+    let (body, suffix) = match v.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&v[..i], Some(c.to_ascii_lowercase())),
+        _ => (v, None),
+    };
+    let tag = match suffix {
+        Some('b') if is_integer(body) => body.parse().ok().map(TagType::Byte),
+        Some('s') if is_integer(body) => body.parse().ok().map(TagType::Short),
+        Some('l') if is_integer(body) => body
+            .parse::<i64>()
+            .ok()
+            .map(|v| TagType::Long(LongNumber::from(v))),
+        Some('f') if is_decimal(body, false) => body.parse().ok().map(TagType::Float),
+        Some('d') if is_decimal(body, false) => body.parse().ok().map(TagType::Double),
+        Some('f') => f32_from_non_finite_str(body).map(TagType::Float),
+        Some('d') => f64_from_non_finite_str(body).map(TagType::Double),
+        None if is_integer(v) => v.parse().ok().map(TagType::Int),
+        None if is_decimal(v, true) => v.parse().ok().map(TagType::Double),
+        _ => None,
+    };
+    match tag {
+        Some(tag) => tag,
+        None if v.eq_ignore_ascii_case("true") => TagType::Byte(1),
+        None if v.eq_ignore_ascii_case("false") => TagType::Byte(0),
+        None => string_tag(v),
+    }
+}
+
+/// Prints a tag as SNBT in the compact form used by Minecraft.
+pub fn to_string(tag: &TagType) -> String {
+    let mut buf = String::new();
+    walk(&mut buf, tag);
+    buf
+}
+
+fn quote(buf: &mut String, v: &str) {
+    let quote = if v.contains('"') && !v.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+    buf.push(quote);
+    for c in v.chars() {
+        if c == '\\' || c == quote {
+            buf.push('\\');
+        }
+        buf.push(c);
+    }
+    buf.push(quote);
+}
+
+// Non-finite values are written like in JSON followed by the suffix, e.g. NaNf and -Infinityd.
+// NaNs lose their bits, as the : of NaN:0x<bits> is not allowed in unquoted strings.
+fn non_finite(v: f64) -> &'static str {
+    match v {
+        v if v.is_nan() => "NaN",
+        v if v.is_sign_positive() => "Infinity",
+        _ => "-Infinity",
+    }
+}
+
+fn walk(buf: &mut String, tag: &TagType) {
+    match tag {
+        TagType::End => {}
+        TagType::Byte(v) => {
+            let _ = write!(buf, "{}b", v);
+        }
+        TagType::Short(v) => {
+            let _ = write!(buf, "{}s", v);
+        }
+        TagType::Int(v) => {
+            let _ = write!(buf, "{}", v);
+        }
+        TagType::Long(v) => {
+            let _ = write!(buf, "{}L", i64::from(v.clone()));
+        }
+        TagType::Float(v) if !v.is_finite() => {
+            let _ = write!(buf, "{}f", non_finite(*v as f64));
+        }
+        TagType::Float(v) => {
+            let _ = write!(buf, "{:?}f", v);
+        }
+        TagType::Double(v) if !v.is_finite() => {
+            let _ = write!(buf, "{}d", non_finite(*v));
+        }
+        TagType::Double(v) => {
+            let _ = write!(buf, "{:?}d", v);
+        }
+        TagType::ByteArray(v) => {
+            buf.push_str("[B;");
+            for (i, v) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push(',');
+                }
+                let _ = write!(buf, "{}B", v);
+            }
+            buf.push(']');
+        }
+        TagType::String(v) => quote(buf, &cesu8_to_string(v)),
+        TagType::List(_, v) => {
+            buf.push('[');
+            for (i, v) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push(',');
+                }
+                walk(buf, v);
+            }
+            buf.push(']');
+        }
+        TagType::Compound(v) => {
+            buf.push('{');
+            for (i, (k, v)) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push(',');
+                }
+                let k = cesu8_to_string(k);
+                if !k.is_empty() && k.chars().all(is_unquoted_char) {
+                    buf.push_str(&k);
+                } else {
+                    quote(buf, &k);
+                }
+                buf.push(':');
+                walk(buf, v);
+            }
+            buf.push('}');
+        }
+        TagType::IntArray(v) => {
+            buf.push_str("[I;");
+            for (i, v) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push(',');
+                }
+                let _ = write!(buf, "{}", v);
+            }
+            buf.push(']');
+        }
+        TagType::LongArray(v) => {
+            buf.push_str("[L;");
+            for (i, v) in v.iter().enumerate() {
+                if i != 0 {
+                    buf.push(',');
+                }
+                let _ = write!(buf, "{}L", i64::from(v.clone()));
+            }
+            buf.push(']');
+        }
+    }
+}
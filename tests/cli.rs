@@ -0,0 +1,189 @@
+use indexmap::IndexMap;
+use nbt_json::json;
+use nbt_json::nbt;
+use nbt_json::nbt::Endianness;
+use nbt_json::nbt::FileType;
+use nbt_json::nbt::NbtMode;
+use nbt_json::nbt::TagType;
+use nbt_json::region;
+use nbt_json::region::Chunk;
+use nbt_json::region::Region;
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+use std::process::Output;
+
+// An empty directory of its own for each test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("nbt-json-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn nbt_json(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_nbt-json"))
+        .args(args)
+        .current_dir(dir)
+        .env("LANG", "en_US.UTF-8")
+        .output()
+        .unwrap()
+}
+
+// level.dat with Difficulty of TAG_Int instead of TAG_Byte
+fn write_level(path: &Path) {
+    let data = TagType::Compound(IndexMap::from([(
+        Cow::Borrowed(&b"Difficulty"[..]),
+        TagType::Int(2),
+    )]));
+    let root = TagType::Compound(IndexMap::from([(Cow::Borrowed(&b"Data"[..]), data)]));
+    let mut nbt = vec![];
+    json::to_nbt(
+        IndexMap::from([(Cow::Borrowed(&b""[..]), root)]),
+        &mut nbt,
+        FileType::GZip,
+        Endianness::Big,
+        NbtMode::File,
+    )
+    .unwrap();
+    fs::write(path, nbt).unwrap();
+}
+
+#[test]
+fn checks_nbt_converted_to_json_against_guessed_schemas() {
+    let dir = temp_dir("guessed-schema");
+    write_level(&dir.join("level.dat"));
+    let output = nbt_json(&dir, &["level.dat"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Mismatch with the schema"), "{}", stderr);
+    assert!(stderr.contains("Data.Difficulty"), "{}", stderr);
+    let json = fs::read_to_string(dir.join("level.json")).unwrap();
+    assert!(json.contains(r#""Difficulty":2"#), "{}", json);
+}
+
+// Bedrock Edition level.dat with the header of storage version 10
+fn write_bedrock_level(path: &Path) {
+    let data = TagType::Compound(IndexMap::from([(
+        Cow::Borrowed(&b"LevelName"[..]),
+        TagType::String(Cow::Borrowed(b"World")),
+    )]));
+    let mut nbt = vec![];
+    json::to_nbt(
+        IndexMap::from([(Cow::Borrowed(&b""[..]), data)]),
+        &mut nbt,
+        FileType::Raw,
+        Endianness::Little,
+        NbtMode::File,
+    )
+    .unwrap();
+    fs::write(path, nbt::put_bedrock_header(10, nbt)).unwrap();
+}
+
+#[test]
+fn keeps_the_header_of_bedrock_level_dat() {
+    let dir = temp_dir("bedrock-header");
+    write_bedrock_level(&dir.join("level.dat"));
+    let original = fs::read(dir.join("level.dat")).unwrap();
+    let output = nbt_json(&dir, &["level.dat", "-e", "bedrock", "-i"]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(dir.join("level.dat")).unwrap(), original);
+
+    let output = nbt_json(&dir, &["level.dat", "-e", "bedrock"]);
+    assert!(output.status.success());
+    let json = fs::read_to_string(dir.join("level.json")).unwrap();
+    assert!(json.contains(r#""$storageVersion":10"#), "{}", json);
+    fs::remove_file(dir.join("level.dat")).unwrap();
+    let output = nbt_json(&dir, &["level.json", "-e", "bedrock"]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(dir.join("level.dat")).unwrap(), original);
+}
+
+#[test]
+fn rejects_in_place_conversions_to_other_formats() {
+    let dir = temp_dir("in-place-format");
+    write_level(&dir.join("level.dat"));
+    let original = fs::read(dir.join("level.dat")).unwrap();
+    for args in [
+        &["level.dat", "--to", "json", "-i"][..],
+        &["to-snbt", "level.dat", "-i"],
+    ] {
+        let output = nbt_json(&dir, args);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("in place"), "{}", stderr);
+        assert_eq!(fs::read(dir.join("level.dat")).unwrap(), original);
+        assert!(!dir.join("level.dat.bak").exists());
+    }
+}
+
+#[test]
+fn fails_only_missing_inputs_of_batches() {
+    let dir = temp_dir("missing-inputs");
+    write_level(&dir.join("a.dat"));
+    let output = nbt_json(&dir, &["a.dat", "missing.dat", "*.dta"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("missing.dat"), "{}", stderr);
+    assert!(
+        stderr.contains("Converted: 0, failed: 2, skipped: 1"),
+        "{}",
+        stderr
+    );
+    assert!(!dir.join("a.json").exists());
+
+    let args = ["a.dat", "missing.dat", "*.dta", "--continue-on-error"];
+    let output = nbt_json(&dir, &args);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Converted: 1, failed: 2, skipped: 0"),
+        "{}",
+        stderr
+    );
+    assert!(dir.join("a.json").exists());
+}
+
+#[test]
+fn keeps_existing_region_dumps() {
+    let dir = temp_dir("region-dump");
+    let root = TagType::Compound(IndexMap::from([(
+        Cow::Borrowed(&b"DataVersion"[..]),
+        TagType::Int(3955),
+    )]));
+    let mut nbt = vec![];
+    json::to_nbt(
+        IndexMap::from([(Cow::Borrowed(&b""[..]), root)]),
+        &mut nbt,
+        FileType::Raw,
+        Endianness::Big,
+        NbtMode::File,
+    )
+    .unwrap();
+    let chunk = Chunk {
+        x: 0,
+        z: 0,
+        timestamp: 0,
+        file_type: FileType::Zlib,
+        nbt,
+    };
+    let region = region::write(&Region {
+        chunks: vec![chunk],
+    })
+    .unwrap();
+    fs::write(dir.join("r.0.0.mca"), region).unwrap();
+    fs::create_dir(dir.join("r.0.0")).unwrap();
+    fs::write(dir.join("r.0.0").join("c.0.0.json"), "{}").unwrap();
+    let output = nbt_json(&dir, &["r.0.0.mca"]);
+    assert!(!output.status.success());
+    let chunk = fs::read_to_string(dir.join("r.0.0").join("c.0.0.json")).unwrap();
+    assert_eq!(chunk, "{}");
+    let output = nbt_json(&dir, &["r.0.0.mca", "-f"]);
+    assert!(output.status.success());
+    let chunk = fs::read_to_string(dir.join("r.0.0").join("c.0.0.json")).unwrap();
+    assert!(chunk.contains("DataVersion"), "{}", chunk);
+}
@@ -0,0 +1,61 @@
+use indexmap::IndexMap;
+use nbt_json::nbt;
+use nbt_json::nbt::TagResolvingError;
+use nbt_json::nbt::TypeHints;
+use nbt_json::nbt::TypeHintsError;
+use nbt_json::snbt;
+use serde_json::Map;
+use serde_json::Value;
+
+// The resolved root tag in SNBT
+fn resolve(json: &str, hints: &TypeHints) -> String {
+    let mut json: Map<String, Value> = serde_json::from_str(json).unwrap();
+    hints.apply(&mut json);
+    let mut root = IndexMap::new();
+    nbt::resolve_tag_types(&mut root, &json).unwrap();
+    snbt::to_string(&root[&b""[..]])
+}
+
+#[test]
+fn applies_hints() {
+    let hints = TypeHints::from_toml(
+        r#"
+"Inventory[].Count" = "byte"
+"Rotation[]" = "float"
+"Pos" = "TAG_List"
+"#,
+    )
+    .unwrap();
+    let tag = resolve(
+        r#"{"": {"Inventory": [{"Count": 1}], "Rotation": [90, 0], "Pos": [0.5], "Count": 1, "Air;S": 300}}"#,
+        &hints,
+    );
+    assert_eq!(
+        tag,
+        "{Inventory:[{Count:1b}],Rotation:[90.0f,0.0f],Pos:[0.5d],Count:1,Air:300s}"
+    );
+}
+
+#[test]
+fn rejects_invalid_hints() {
+    assert_eq!(
+        TypeHints::from_json(r#"{"Pos[0]": "double"}"#),
+        Err(TypeHintsError::InvalidPath("Pos[0]".to_string()))
+    );
+    assert_eq!(
+        TypeHints::from_json(r#"{"Count": "bite"}"#),
+        Err(TypeHintsError::UnknownType("bite".to_string()))
+    );
+}
+
+#[test]
+fn keeps_duplicate_names_for_the_resolver() {
+    let hints = TypeHints::from_toml(r#""a" = "byte""#).unwrap();
+    let mut json: Map<String, Value> = serde_json::from_str(r#"{"": {"a;B": 1, "a": 2}}"#).unwrap();
+    hints.apply(&mut json);
+    let mut root = IndexMap::new();
+    assert_eq!(
+        nbt::resolve_tag_types(&mut root, &json),
+        Err(TagResolvingError::DuplicateName("a".to_string()))
+    );
+}
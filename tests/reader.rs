@@ -0,0 +1,265 @@
+use indexmap::IndexMap;
+use nbt_json::json;
+use nbt_json::json::Indent;
+use nbt_json::json::JsonStyle;
+use nbt_json::nbt;
+use nbt_json::nbt::Endianness;
+use nbt_json::nbt::FileType;
+use nbt_json::nbt::LongNumber;
+use nbt_json::nbt::NbtEvent;
+use nbt_json::nbt::NbtMode;
+use nbt_json::nbt::NbtReader;
+use nbt_json::nbt::TagType;
+use serde_json::Map;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::io;
+
+fn sample() -> IndexMap<Cow<'static, [u8]>, TagType<'static>> {
+    let pos = TagType::List(0x06, vec![TagType::Double(1.0), TagType::Double(2.0)]);
+    let root = TagType::Compound(IndexMap::from([
+        (Cow::Borrowed(&b"Count"[..]), TagType::Byte(1)),
+        (Cow::Borrowed(&b"Pos"[..]), pos),
+    ]));
+    IndexMap::from([(Cow::Borrowed(&b"root"[..]), root)])
+}
+
+#[test]
+fn yields_events_in_order() {
+    let mut nbt = vec![];
+    json::to_nbt(
+        sample(),
+        &mut nbt,
+        FileType::GZip,
+        Endianness::Big,
+        NbtMode::File,
+    )
+    .unwrap();
+    let reader = FileType::GZip.decoder(&nbt[..]).unwrap();
+    let events = NbtReader::new(reader, Endianness::Big, NbtMode::File)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let name = |name: &str| Some(name.to_string());
+    assert_eq!(
+        events,
+        vec![
+            NbtEvent::CompoundStart { name: name("root") },
+            NbtEvent::Value {
+                name: name("Count"),
+                value: TagType::Byte(1),
+            },
+            NbtEvent::ListStart {
+                name: name("Pos"),
+                item_type: 0x06,
+                len: 2,
+            },
+            NbtEvent::Value {
+                name: None,
+                value: TagType::Double(1.0),
+            },
+            NbtEvent::Value {
+                name: None,
+                value: TagType::Double(2.0),
+            },
+            NbtEvent::End,
+            NbtEvent::End,
+        ]
+    );
+}
+
+#[test]
+fn reports_the_path_of_truncated_tags() {
+    let mut nbt = vec![];
+    json::to_nbt(
+        sample(),
+        &mut nbt,
+        FileType::Raw,
+        Endianness::Big,
+        NbtMode::File,
+    )
+    .unwrap();
+    nbt.truncate(nbt.len() - 4);
+    let mut reader = NbtReader::new(&nbt[..], Endianness::Big, NbtMode::File);
+    let err = reader.find_map(Result::err).unwrap();
+    assert_eq!(err.path, "Pos[1]");
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn reads_arrays_in_bulk() {
+    let ints = (0..40000)
+        .map(|v: i32| v.wrapping_mul(65537))
+        .collect::<Vec<_>>();
+    let longs = ints
+        .iter()
+        .map(|v| LongNumber::from(*v as i64 * -3))
+        .collect();
+    let bytes = ints.iter().map(|v| *v as i8).collect();
+    let root = TagType::Compound(IndexMap::from([
+        (Cow::Borrowed(&b"b"[..]), TagType::ByteArray(bytes)),
+        (Cow::Borrowed(&b"i"[..]), TagType::IntArray(ints)),
+        (Cow::Borrowed(&b"l"[..]), TagType::LongArray(longs)),
+    ]));
+    let root = IndexMap::from([(Cow::Borrowed(&b""[..]), root)]);
+    for endianness in [Endianness::Big, Endianness::Little] {
+        let mut nbt = vec![];
+        json::to_nbt(
+            root.clone(),
+            &mut nbt,
+            FileType::Raw,
+            endianness,
+            NbtMode::File,
+        )
+        .unwrap();
+        assert_eq!(nbt::decode(&nbt, endianness, NbtMode::File).unwrap(), root);
+        let err = nbt::decode(&nbt[..nbt.len() - 2], endianness, NbtMode::File).unwrap_err();
+        assert_eq!(err.path, "l");
+    }
+}
+
+// Tags of every kind, which the emitter writes as they are read or as a whole
+fn mixed() -> IndexMap<Cow<'static, [u8]>, TagType<'static>> {
+    let compound = |entries: Vec<(&'static str, TagType<'static>)>| {
+        TagType::Compound(
+            entries
+                .into_iter()
+                .map(|(k, v)| (Cow::Borrowed(k.as_bytes()), v))
+                .collect(),
+        )
+    };
+    let item = compound(vec![
+        ("id", TagType::String(Cow::Borrowed(b"minecraft:stone"))),
+        ("Count", TagType::Byte(1)),
+        ("tag", compound(vec![])),
+    ]);
+    let nested = TagType::List(
+        0x09,
+        vec![
+            TagType::List(0x00, vec![]),
+            TagType::List(0x02, vec![TagType::Short(3)]),
+        ],
+    );
+    let root = compound(vec![
+        (
+            "Items",
+            TagType::List(0x0a, vec![item.clone(), compound(vec![])]),
+        ),
+        ("Empty", TagType::List(0x0a, vec![])),
+        ("Nested", nested),
+        (
+            "Pos",
+            TagType::List(0x06, vec![TagType::Double(0.5), TagType::Double(f64::NAN)]),
+        ),
+        (
+            "Rotation",
+            TagType::List(0x05, vec![TagType::Float(1.5), TagType::Float(2.0)]),
+        ),
+        (
+            "a;b",
+            compound(vec![("c;d", TagType::Long(LongNumber::from(i64::MAX)))]),
+        ),
+        ("Bytes", TagType::ByteArray(vec![1, -1])),
+        ("UUID", TagType::IntArray(vec![1, 2, 3, 4])),
+        ("Longs", TagType::LongArray(vec![LongNumber::from(1)])),
+        (
+            "Deep",
+            compound(vec![("Items", TagType::List(0x0a, vec![item]))]),
+        ),
+    ]);
+    IndexMap::from([(Cow::Borrowed(&b"$root"[..]), root)])
+}
+
+#[test]
+fn emits_json_as_it_is_read() {
+    let mut nbt = vec![];
+    json::to_nbt(
+        mixed(),
+        &mut nbt,
+        FileType::GZip,
+        Endianness::Big,
+        NbtMode::File,
+    )
+    .unwrap();
+    let styles = [
+        JsonStyle::default(),
+        JsonStyle {
+            indent: Some(Indent::default()),
+            compact_numeric_arrays: false,
+        },
+        JsonStyle {
+            indent: Some(Indent::Tab),
+            compact_numeric_arrays: true,
+        },
+    ];
+    for style in styles {
+        let mut json = Map::new();
+        nbt::tag_types_to_json(&mixed(), &mut json);
+        nbt::set_compression(&mut json, FileType::GZip);
+        let expected = json::to_string(&Value::Object(json), &style);
+        let (file_type, decoder) = nbt::detect_decoder(&nbt[..]).unwrap();
+        assert_eq!(file_type, FileType::GZip);
+        let mut reader = NbtReader::new(decoder, Endianness::Big, NbtMode::File);
+        let json = nbt::write_json(&mut reader, vec![], Some(file_type), style).unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), expected);
+    }
+}
+
+#[test]
+fn emits_empty_documents() {
+    let mut reader = NbtReader::new(&[0x00][..], Endianness::Big, NbtMode::File);
+    let json = nbt::write_json(&mut reader, vec![], None, JsonStyle::default()).unwrap();
+    assert_eq!(json, br#"{"$version":1}"#);
+}
+
+#[test]
+fn quotes_names_in_the_paths_of_truncated_tags() {
+    let display = TagType::Compound(IndexMap::from([(
+        Cow::Borrowed(&b"minecraft:custom.name"[..]),
+        TagType::List(0x03, vec![TagType::Int(1), TagType::Int(2)]),
+    )]));
+    let root = TagType::Compound(IndexMap::from([(Cow::Borrowed(&b"tag"[..]), display)]));
+    let mut nbt = vec![];
+    json::to_nbt(
+        IndexMap::from([(Cow::Borrowed(&b""[..]), root)]),
+        &mut nbt,
+        FileType::Raw,
+        Endianness::Big,
+        NbtMode::File,
+    )
+    .unwrap();
+    let err = nbt::decode(&nbt[..nbt.len() - 5], Endianness::Big, NbtMode::File).unwrap_err();
+    assert_eq!(err.path, r#"tag."minecraft:custom.name"[1]"#);
+}
+
+// An LZ4Block header of lz4-java
+fn lz4_header(token: u8, compressed_len: u32, len: u32) -> Vec<u8> {
+    let mut buf = b"LZ4Block".to_vec();
+    buf.push(token);
+    buf.extend(compressed_len.to_le_bytes());
+    buf.extend(len.to_le_bytes());
+    buf.extend(0u32.to_le_bytes());
+    buf
+}
+
+#[test]
+fn rejects_truncated_lz4_blocks() {
+    let mut lz4 = lz4_header(0x26, 16, 32);
+    lz4.extend([0; 8]);
+    let err = FileType::Lz4.decompress(lz4).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn rejects_oversized_lz4_blocks() {
+    // 4 GiB, beyond the 64 KiB of level 6 and the 32 MiB of any level
+    for token in [0x26, 0x2f] {
+        let err = FileType::Lz4
+            .decompress(lz4_header(token, 16, u32::MAX))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+    let err = FileType::Lz4
+        .decompress(lz4_header(0x26, u32::MAX, 16))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
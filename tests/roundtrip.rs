@@ -0,0 +1,236 @@
+use indexmap::IndexMap;
+use nbt_json::json;
+use nbt_json::json::Indent;
+use nbt_json::json::JsonStyle;
+use nbt_json::nbt;
+use nbt_json::nbt::Endianness;
+use nbt_json::nbt::FileType;
+use nbt_json::nbt::JsonFormat;
+use nbt_json::nbt::LongNumber;
+use nbt_json::nbt::NbtMode;
+use nbt_json::nbt::TagResolvingError;
+use nbt_json::nbt::TagType;
+use proptest::prelude::*;
+use serde_json::Map;
+use serde_json::Value;
+use std::borrow::Cow;
+
+fn cesu8(v: String) -> Cow<'static, [u8]> {
+    Cow::Owned(cesu8::to_java_cesu8(&v).into_owned())
+}
+
+// Any name, including ones with ; and ones looking like type suffixes
+fn name() -> impl Strategy<Value = String> {
+    "(.|;[BSLFDI]?){0,8}"
+}
+
+fn tag() -> impl Strategy<Value = TagType<'static>> {
+    let leaf = prop_oneof![
+        any::<i8>().prop_map(TagType::Byte),
+        any::<i16>().prop_map(TagType::Short),
+        any::<i32>().prop_map(TagType::Int),
+        any::<i64>().prop_map(|v| TagType::Long(LongNumber::from(v))),
+        // Bits, so that NaNs with any payload and infinities are generated too
+        any::<u32>().prop_map(|v| TagType::Float(f32::from_bits(v))),
+        any::<u64>().prop_map(|v| TagType::Double(f64::from_bits(v))),
+        prop::collection::vec(any::<i8>(), 0..8).prop_map(TagType::ByteArray),
+        any::<String>().prop_map(|v| TagType::String(cesu8(v))),
+        prop::collection::vec(any::<i32>(), 0..8).prop_map(TagType::IntArray),
+        prop::collection::vec(any::<i64>().prop_map(LongNumber::from), 0..8)
+            .prop_map(TagType::LongArray),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            // Items of another type than the first one are dropped, as lists are homogeneous.
+            (prop::collection::vec(inner.clone(), 0..6), 0..=0x0cu8).prop_map(
+                |(mut items, empty_type)| {
+                    let item_type = items.first().map_or(empty_type, u8::from);
+                    items.retain(|v| u8::from(v) == item_type);
+                    TagType::List(item_type, items)
+                }
+            ),
+            prop::collection::vec((name(), inner), 0..6).prop_map(|entries| {
+                TagType::Compound(IndexMap::from_iter(
+                    entries.into_iter().map(|(k, v)| (cesu8(k), v)),
+                ))
+            }),
+        ]
+    })
+}
+
+fn root() -> impl Strategy<Value = IndexMap<Cow<'static, [u8]>, TagType<'static>>> {
+    (name(), tag()).prop_map(|(k, v)| IndexMap::from([(cesu8(k), v)]))
+}
+
+fn encode(root: IndexMap<Cow<[u8]>, TagType>, endianness: Endianness, mode: NbtMode) -> Vec<u8> {
+    let mut nbt = vec![];
+    json::to_nbt(root, &mut nbt, FileType::Raw, endianness, mode).unwrap();
+    nbt
+}
+
+// NBT -> JSON text -> NBT
+fn round_trip(
+    nbt: &[u8],
+    style: &JsonStyle,
+    format: JsonFormat,
+    endianness: Endianness,
+    mode: NbtMode,
+) -> Vec<u8> {
+    let root = nbt::decode(nbt, endianness, mode).unwrap();
+    let mut json = Map::new();
+    nbt::tag_types_to_json_as(&root, &mut json, format);
+    let json = json::to_string(&Value::Object(json), style);
+    let json: Map<String, Value> = serde_json::from_str(&json).unwrap();
+    let mut root = IndexMap::new();
+    nbt::resolve_tag_types(&mut root, &json).unwrap();
+    encode(root, endianness, mode)
+}
+
+proptest! {
+    #[test]
+    fn java_nbt_round_trips(mut root in root()) {
+        root.values_mut().for_each(TagType::sort_keys);
+        let nbt = encode(root, Endianness::Big, NbtMode::File);
+        let json = round_trip(&nbt, &JsonStyle::default(), JsonFormat::Compact, Endianness::Big, NbtMode::File);
+        prop_assert_eq!(json, nbt);
+    }
+
+    #[test]
+    fn bedrock_network_nbt_round_trips(mut root in root()) {
+        root.values_mut().for_each(TagType::sort_keys);
+        let nbt = encode(root, Endianness::Little, NbtMode::Network);
+        let style = JsonStyle {
+            indent: Some(Indent::Tab),
+            compact_numeric_arrays: true,
+        };
+        let json = round_trip(&nbt, &style, JsonFormat::Compact, Endianness::Little, NbtMode::Network);
+        prop_assert_eq!(json, nbt);
+    }
+
+    #[test]
+    fn typed_json_round_trips(mut root in root()) {
+        root.values_mut().for_each(TagType::sort_keys);
+        let nbt = encode(root, Endianness::Big, NbtMode::File);
+        let json = round_trip(&nbt, &JsonStyle::default(), JsonFormat::Typed, Endianness::Big, NbtMode::File);
+        prop_assert_eq!(json, nbt);
+    }
+}
+
+// The keys of tags named with ;, which is doubled in names
+#[test]
+fn escapes_names() {
+    let root = IndexMap::from([(
+        cesu8("".to_string()),
+        TagType::Compound(IndexMap::from([
+            (cesu8("a;B".to_string()), TagType::Int(1)),
+            (cesu8(";".to_string()), TagType::Byte(2)),
+            (
+                cesu8("x;;y".to_string()),
+                TagType::String(cesu8("z".to_string())),
+            ),
+            (cesu8("".to_string()), TagType::List(0x03, vec![])),
+        ])),
+    )]);
+    let mut json = Map::new();
+    nbt::tag_types_to_json(&root, &mut json);
+    assert_eq!(
+        Value::Object(json.clone()).to_string(),
+        r#"{"$version":1,"":{"a;;B":1,";;;B":2,"x;;;;y":"z",";TAG_Int":[]}}"#
+    );
+    let mut resolved = IndexMap::new();
+    nbt::resolve_tag_types(&mut resolved, &json).unwrap();
+    assert_eq!(resolved, root);
+}
+
+// The compression of the source NBT, recorded next to the version
+#[test]
+fn records_compression() {
+    let root = IndexMap::from([(
+        cesu8("Data".to_string()),
+        TagType::Compound(IndexMap::from([(cesu8("a".to_string()), TagType::Int(1))])),
+    )]);
+    let mut json = Map::new();
+    nbt::tag_types_to_json_as(&root, &mut json, JsonFormat::Typed);
+    nbt::set_compression(&mut json, FileType::GZip);
+    assert_eq!(
+        json.keys().collect::<Vec<_>>(),
+        ["$version", "$compression", "$format", "Data"]
+    );
+    assert_eq!(nbt::compression(&json), Ok(Some(FileType::GZip)));
+    let mut resolved = IndexMap::new();
+    nbt::resolve_tag_types(&mut resolved, &json).unwrap();
+    assert_eq!(resolved, root);
+
+    json.insert("$compression".to_string(), Value::from("bzip2"));
+    assert!(nbt::compression(&json).is_err());
+    json.remove("$compression");
+    assert_eq!(nbt::compression(&json), Ok(None));
+}
+
+// The storage version of Bedrock Edition level.dat, recorded next to the compression
+#[test]
+fn records_storage_version() {
+    let root = IndexMap::from([(cesu8("".to_string()), TagType::Compound(IndexMap::new()))]);
+    let mut json = Map::new();
+    nbt::tag_types_to_json_as(&root, &mut json, JsonFormat::Typed);
+    nbt::set_storage_version(&mut json, 10);
+    nbt::set_compression(&mut json, FileType::Raw);
+    assert_eq!(
+        json.keys().collect::<Vec<_>>(),
+        ["$version", "$compression", "$storageVersion", "$format", ""]
+    );
+    assert_eq!(nbt::storage_version(&json), Ok(Some(10)));
+    let mut resolved = IndexMap::new();
+    nbt::resolve_tag_types(&mut resolved, &json).unwrap();
+    assert_eq!(resolved, root);
+
+    json.insert("$storageVersion".to_string(), Value::from(-1));
+    assert!(nbt::storage_version(&json).is_err());
+}
+
+// Root names starting with $, whose keys are escaped so as not to clash with the metadata
+#[test]
+fn escapes_root_names() {
+    for name in ["$version", "$$", "$x;B"] {
+        let root = IndexMap::from([(cesu8(name.to_string()), TagType::Byte(1))]);
+        for format in [JsonFormat::Compact, JsonFormat::Typed] {
+            let mut json = Map::new();
+            nbt::tag_types_to_json_as(&root, &mut json, format);
+            assert_eq!(json["$version"], 1);
+            let mut resolved = IndexMap::new();
+            nbt::resolve_tag_types(&mut resolved, &json).unwrap();
+            assert_eq!(resolved, root);
+        }
+    }
+    let root = IndexMap::from([(
+        cesu8("$version".to_string()),
+        TagType::Compound(IndexMap::new()),
+    )]);
+    let mut json = Map::new();
+    nbt::tag_types_to_json(&root, &mut json);
+    assert_eq!(
+        Value::Object(json).to_string(),
+        r#"{"$version":1,"$$version":{}}"#
+    );
+}
+
+// Keys naming the same tag, which would replace one another
+#[test]
+fn rejects_duplicate_names() {
+    for (json, path) in [
+        (r#"{"": {"a;B": 1, "a;S": 2}}"#, "a"),
+        (r#"{"": {"x;;y": 1, "x;;y;B": 2}}"#, "x;y"),
+        (
+            r#"{"": {"Data": {"Items;TAG_Compound": [{}, {"b": 1, "b;L": 2}]}}}"#,
+            "Data.Items[1].b",
+        ),
+        (r#"{"": {"a.b;B": 1, "a.b;S": 2}}"#, "\"a.b\""),
+    ] {
+        let json: Map<String, Value> = serde_json::from_str(json).unwrap();
+        let mut root = IndexMap::new();
+        assert_eq!(
+            nbt::resolve_tag_types(&mut root, &json),
+            Err(TagResolvingError::DuplicateName(path.to_string()))
+        );
+    }
+}
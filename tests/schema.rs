@@ -0,0 +1,124 @@
+use clap::ValueEnum;
+use nbt_json::nbt::Schema;
+use nbt_json::nbt::SchemaViolation;
+use nbt_json::nbt::SchemaViolationKind;
+use nbt_json::nbt::TypeHints;
+use nbt_json::snbt;
+use std::path::Path;
+
+#[test]
+fn loads_bundled_schemas() {
+    for schema in Schema::value_variants() {
+        assert!(!schema.hints().is_empty());
+    }
+}
+
+#[test]
+fn guesses_schemas_by_file_name() {
+    let guess = |v: &str| Schema::from_path(Path::new(v));
+    assert_eq!(guess("world/level.dat"), Some(Schema::Level));
+    assert_eq!(guess("level.dat_old"), Some(Schema::Level));
+    assert_eq!(guess("level.json"), Some(Schema::Level));
+    assert_eq!(
+        guess("playerdata/069a79f4-44e9-4726-a5be-fca90e38aaf5.dat"),
+        Some(Schema::Player)
+    );
+    assert_eq!(guess("house.nbt"), Some(Schema::Structure));
+    assert_eq!(guess("house.json"), None);
+}
+
+#[test]
+fn validates_tags() {
+    let tag = snbt::parse(
+        "{size:[1,1,1],palette:[{Name:\"minecraft:stone\"}],blocks:[{pos:[0,0,0],state:0s}],\
+         entities:[{pos:[0.5d,0.0d,0.5d],nbt:{id:\"minecraft:pig\",Health:10.0f,Motion:[0.0f]}}],\
+         foo:1b}",
+    )
+    .unwrap();
+    let violations = Schema::Structure.hints().validate(&tag);
+    assert_eq!(
+        violations,
+        [
+            SchemaViolation {
+                path: "blocks[0].state".to_string(),
+                kind: SchemaViolationKind::MismatchedType {
+                    expected: vec![0x03],
+                    actual: 0x02
+                },
+            },
+            SchemaViolation {
+                path: "entities[0].nbt.Motion[0]".to_string(),
+                kind: SchemaViolationKind::MismatchedType {
+                    expected: vec![0x06],
+                    actual: 0x05
+                },
+            },
+            SchemaViolation {
+                path: "foo".to_string(),
+                kind: SchemaViolationKind::UnknownTag,
+            },
+            SchemaViolation {
+                path: "DataVersion".to_string(),
+                kind: SchemaViolationKind::MissingTag,
+            },
+        ]
+    );
+}
+
+#[test]
+fn validates_required_tags_and_ranges() {
+    let hints = TypeHints::from_toml(
+        r#"
+"Difficulty" = { type = "byte", min = 0, max = 3 }
+"Inventory[].id" = { type = "string", required = true }
+"Seed" = { type = ["int", "long"], required = true }
+"#,
+    )
+    .unwrap();
+    let tag =
+        snbt::parse("{Difficulty:4b,Inventory:[{id:\"minecraft:stone\"},{Count:1b}]}").unwrap();
+    let violations = hints.validate(&tag);
+    assert_eq!(
+        violations,
+        [
+            SchemaViolation {
+                path: "Difficulty".to_string(),
+                kind: SchemaViolationKind::OutOfRange {
+                    value: 4.0,
+                    min: Some(0.0),
+                    max: Some(3.0)
+                },
+            },
+            SchemaViolation {
+                path: "Inventory[1].id".to_string(),
+                kind: SchemaViolationKind::MissingTag,
+            },
+            SchemaViolation {
+                path: "Seed".to_string(),
+                kind: SchemaViolationKind::MissingTag,
+            },
+        ]
+    );
+    assert_eq!(
+        violations[0].to_string(),
+        "Tag \"Difficulty\" is 4, but 0 to 3 is expected."
+    );
+    let tag = snbt::parse("{Seed:1s}").unwrap();
+    assert_eq!(
+        hints.validate(&tag)[0].to_string(),
+        "Tag \"Seed\" is TAG_Short, but TAG_Int or TAG_Long is expected."
+    );
+}
+
+#[test]
+fn keeps_level_data_open() {
+    let tag = snbt::parse("{Data:{Time:1L,ModdedKey:1b,Version:{Id:1,foo:1b}}}").unwrap();
+    let violations = Schema::Level.hints().validate(&tag);
+    assert_eq!(
+        violations,
+        [SchemaViolation {
+            path: "Data.Version.foo".to_string(),
+            kind: SchemaViolationKind::UnknownTag,
+        }]
+    );
+}
@@ -0,0 +1,62 @@
+use nbt_json::nbt;
+use nbt_json::nbt::Endianness;
+use nbt_json::nbt::IntArray;
+use nbt_json::nbt::NbtMode;
+use nbt_json::nbt::TagType;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Player {
+    data_version: i32,
+    pos: Vec<f64>,
+    on_ground: bool,
+    custom_name: Option<String>,
+    #[serde(rename = "UUID")]
+    uuid: IntArray,
+    inventory: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Item {
+    id: String,
+    #[serde(rename = "Count")]
+    count: i8,
+}
+
+#[test]
+fn round_trips_structs() {
+    let player = Player {
+        data_version: 3700,
+        pos: vec![0.5, 64.0, -0.5],
+        on_ground: true,
+        custom_name: None,
+        uuid: IntArray(vec![1, 2, 3, 4]),
+        inventory: vec![Item {
+            id: "minecraft:stone".to_string(),
+            count: 64,
+        }],
+    };
+    let buf = nbt::to_vec(&player).unwrap();
+    let root = nbt::decode(&buf, Endianness::Big, NbtMode::File).unwrap();
+    let TagType::Compound(root) = &root[&b""[..]] else {
+        panic!("root is not a compound");
+    };
+    assert_eq!(root[&b"OnGround"[..]], TagType::Byte(1));
+    assert_eq!(root[&b"UUID"[..]], TagType::IntArray(vec![1, 2, 3, 4]));
+    assert!(!root.contains_key(&b"CustomName"[..]));
+    assert_eq!(nbt::from_slice::<Player>(&buf).unwrap(), player);
+}
+
+#[test]
+fn rejects_mixed_lists() {
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Value {
+        Int(i32),
+        String(String),
+    }
+    let values = vec![Value::Int(1), Value::String("a".to_string())];
+    assert!(nbt::to_vec(&values).is_err());
+}
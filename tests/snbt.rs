@@ -0,0 +1,35 @@
+use nbt_json::nbt::TagType;
+use nbt_json::snbt;
+
+#[test]
+fn roundtrips_non_finite_floats() {
+    let list = |item_type, items| TagType::List(item_type, items);
+    let floats = list(
+        0x05,
+        vec![
+            TagType::Float(f32::INFINITY),
+            TagType::Float(f32::NEG_INFINITY),
+            TagType::Float(1.5),
+        ],
+    );
+    let doubles = list(
+        0x06,
+        vec![
+            TagType::Double(f64::INFINITY),
+            TagType::Double(f64::NEG_INFINITY),
+            TagType::Double(-0.5),
+        ],
+    );
+    for tag in [floats, doubles] {
+        let snbt = snbt::to_string(&tag);
+        assert_eq!(snbt::parse(&snbt).unwrap(), tag, "{}", snbt);
+    }
+    assert_eq!(snbt::to_string(&TagType::Float(f32::NAN)), "NaNf");
+    assert_eq!(
+        snbt::to_string(&TagType::Double(-f64::INFINITY)),
+        "-Infinityd"
+    );
+    assert!(matches!(snbt::parse("NaNf"), Ok(TagType::Float(v)) if v.is_nan()));
+    assert!(matches!(snbt::parse("NaND"), Ok(TagType::Double(v)) if v.is_nan()));
+    assert!(matches!(snbt::parse("NaN"), Ok(TagType::String(_))));
+}
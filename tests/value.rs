@@ -0,0 +1,110 @@
+use indexmap::IndexMap;
+use nbt_json::nbt;
+use nbt_json::nbt::Endianness;
+use nbt_json::nbt::NbtCompound;
+use nbt_json::nbt::NbtMode;
+use nbt_json::nbt::NbtValue;
+use nbt_json::nbt::NbtWriter;
+use nbt_json::nbt::TagDiff;
+use nbt_json::nbt::TagDiffKind;
+use nbt_json::nbt::TagType;
+use std::borrow::Cow;
+
+fn level() -> NbtCompound {
+    let mut player = NbtCompound::new();
+    player.insert("Pos", vec![0.5, 64.0, -0.5]);
+    player.insert("minecraft:custom.name", "Steve");
+    let mut data = NbtCompound::new();
+    data.insert("Player", player);
+    let mut level = NbtCompound::new();
+    level.insert("Data", data);
+    level
+}
+
+#[test]
+fn gets_nested_tags() {
+    let level = level();
+    assert_eq!(
+        level.get_path("Data.Player.Pos[2]"),
+        Some(&NbtValue::Double(-0.5))
+    );
+    assert_eq!(
+        level
+            .get_path("Data.Player.\"minecraft:custom.name\"")
+            .and_then(NbtValue::as_str),
+        Some("Steve")
+    );
+    assert_eq!(level["Data"]["Player"]["Pos"][0].as_double(), Some(0.5));
+    assert_eq!(level.get_path("Data.Player.Pos[3]"), None);
+    assert_eq!(level.get_path("Data.Player.Pos[x]"), None);
+}
+
+#[test]
+fn sets_nested_tags() {
+    let mut level = NbtValue::from(level());
+    level.set_path("Data.Player.Pos[0]", 1.5).unwrap();
+    level.set_path("Data.Player.Pos[3]", 2.0).unwrap();
+    level.set_path("Data.Time", 100i64).unwrap();
+    assert_eq!(level["Data"]["Player"]["Pos"][0].as_double(), Some(1.5));
+    assert_eq!(level["Data"]["Player"]["Pos"][3].as_double(), Some(2.0));
+    assert_eq!(level["Data"]["Time"].as_long(), Some(100));
+    assert_eq!(
+        level.set_path("Data.Player.Pos[0]", 1),
+        Err(NbtValue::Int(1))
+    );
+    assert_eq!(level.set_path("Data.Nope.Time", 1), Err(NbtValue::Int(1)));
+    assert_eq!(
+        level.set_path("Data.Player.Pos[5]", 1.0),
+        Err(NbtValue::Double(1.0))
+    );
+}
+
+#[test]
+fn diffs_tags() {
+    let a = NbtValue::from(level());
+    let mut b = a.clone();
+    b.set_path("Data.Player.Pos[1]", 65.0).unwrap();
+    b.set_path("Data.Time", 100i64).unwrap();
+    b["Data"]["Player"]
+        .as_compound_mut()
+        .unwrap()
+        .remove("minecraft:custom.name");
+    assert_eq!(
+        a.diff(&b),
+        [
+            TagDiff {
+                path: "Data.Player.Pos[1]".to_string(),
+                kind: TagDiffKind::Changed(NbtValue::Double(64.0), NbtValue::Double(65.0)),
+            },
+            TagDiff {
+                path: "Data.Player.\"minecraft:custom.name\"".to_string(),
+                kind: TagDiffKind::Removed(NbtValue::from("Steve")),
+            },
+            TagDiff {
+                path: "Data.Time".to_string(),
+                kind: TagDiffKind::Added(NbtValue::Long(100)),
+            },
+        ]
+    );
+    assert!(a.diff(&a).is_empty());
+}
+
+#[test]
+fn converts_from_and_to_tag_types() {
+    let mut root = NbtCompound::new();
+    root.insert("", level());
+    assert_eq!(
+        root.get_path(".Data.Player.Pos[1]"),
+        Some(&NbtValue::Double(64.0))
+    );
+    let tags: IndexMap<Cow<[u8]>, TagType> = root.clone().into();
+    let mut w = NbtWriter::new(vec![], Endianness::Big, NbtMode::File);
+    for (name, tag) in &tags {
+        w.write_tag(&String::from_utf8_lossy(name), tag).unwrap();
+    }
+    let buf = w.finish().unwrap();
+    let decoded = nbt::decode(&buf, Endianness::Big, NbtMode::File).unwrap();
+    assert_eq!(decoded, tags);
+    assert_eq!(NbtCompound::try_from(decoded).unwrap(), root);
+    assert!(NbtValue::try_from(TagType::End).is_err());
+}
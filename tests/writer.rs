@@ -0,0 +1,80 @@
+use nbt_json::nbt;
+use nbt_json::nbt::CompressionOptions;
+use nbt_json::nbt::Endianness;
+use nbt_json::nbt::FileType;
+use nbt_json::nbt::GzipHeader;
+use nbt_json::nbt::NbtEncodeError;
+use nbt_json::nbt::NbtMode;
+use nbt_json::nbt::NbtWriter;
+use nbt_json::nbt::TagType;
+
+#[test]
+fn writes_nested_tags() {
+    let mut w = NbtWriter::new(vec![], Endianness::Big, NbtMode::File);
+    w.begin_compound("").unwrap();
+    w.write_int("DataVersion", 3700).unwrap();
+    w.begin_list("Pos", 0x06, 2).unwrap();
+    w.write_double("", 1.0).unwrap();
+    w.write_double("", 2.0).unwrap();
+    w.end().unwrap();
+    w.end().unwrap();
+    let buf = w.finish().unwrap();
+    let root = nbt::decode(&buf, Endianness::Big, NbtMode::File).unwrap();
+    let TagType::Compound(root) = &root[&b""[..]] else {
+        panic!("root is not a compound");
+    };
+    assert_eq!(root[&b"DataVersion"[..]], TagType::Int(3700));
+    assert_eq!(
+        root[&b"Pos"[..]],
+        TagType::List(0x06, vec![TagType::Double(1.0), TagType::Double(2.0)])
+    );
+}
+
+#[test]
+fn rejects_malformed_structures() {
+    let mut w = NbtWriter::new(vec![], Endianness::Big, NbtMode::File);
+    w.begin_list("", 0x03, 1).unwrap();
+    assert_eq!(
+        w.write_string("", "a"),
+        Err(NbtEncodeError::ListItemTypeMismatch {
+            expected: 0x03,
+            actual: 0x08,
+        })
+    );
+    assert_eq!(
+        w.end(),
+        Err(NbtEncodeError::ListLengthMismatch {
+            expected: 1,
+            actual: 0,
+        })
+    );
+    assert_eq!(w.end(), Err(NbtEncodeError::UnexpectedEnd));
+
+    let mut w = NbtWriter::new(vec![], Endianness::Big, NbtMode::File);
+    w.begin_compound("").unwrap();
+    assert_eq!(w.finish().err(), Some(NbtEncodeError::UnclosedTag));
+}
+
+#[test]
+fn compresses_with_options() {
+    let nbt = vec![
+        0x0a, 0x00, 0x00, 0x03, 0x00, 0x01, b'a', 0x00, 0x00, 0x00, 0x01, 0x00,
+    ];
+    let stored = CompressionOptions {
+        level: 0,
+        header: GzipHeader::Zeroed,
+    };
+    let gzip = FileType::GZip.compress_with(nbt.clone(), &stored);
+    // The modification time after the method and flags, and the operating system
+    assert_eq!(gzip[4..8], [0, 0, 0, 0]);
+    assert_eq!(gzip[9], 255);
+    assert_eq!(gzip, FileType::GZip.compress_with(nbt.clone(), &stored));
+    assert_eq!(FileType::GZip.decompress(gzip).unwrap(), nbt);
+    let current = CompressionOptions {
+        level: 9,
+        header: GzipHeader::Current,
+    };
+    let gzip = FileType::GZip.compress_with(nbt.clone(), &current);
+    assert_ne!(gzip[4..8], [0, 0, 0, 0]);
+    assert_eq!(FileType::GZip.decompress(gzip).unwrap(), nbt);
+}